@@ -0,0 +1,115 @@
+use std::{net::IpAddr, path::PathBuf, time::Duration};
+
+use log::trace;
+use reqwest::{Certificate, ClientBuilder, Proxy};
+
+use crate::Result;
+
+/// The `User-Agent` sent when none is configured
+pub const DEFAULT_USER_AGENT: &str = concat!("mirrors-arch/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Network settings applied to every HTTP request made by a [Client]
+///
+/// # Example
+///
+/// ```rust
+/// # use mirrors_arch::{get_client_with_config, ClientConfig};
+/// # fn foo() -> Result<(), Box<dyn std::error::Error>> {
+/// let config = ClientConfig {
+///     timeout: Some(5),
+///     https_proxy: Some(String::from("socks5h://127.0.0.1:1080")),
+///     user_agent: Some(String::from("my-app/1.0")),
+///     ..Default::default()
+/// };
+/// let client = get_client_with_config(&config)?;
+/// #  Ok(())
+/// # }
+/// ```
+pub struct ClientConfig {
+    /// Connection timeout (in seconds)
+    pub timeout: Option<u64>,
+    /// A proxy used for all traffic. `http://`, `https://` and `socks5://`
+    /// (or `socks5h://`) proxies are supported
+    pub proxy: Option<String>,
+    /// A proxy used for `http` URLs only
+    pub http_proxy: Option<String>,
+    /// A proxy used for `https` URLs only
+    pub https_proxy: Option<String>,
+    /// Ignore the `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY`
+    /// environment variables, which are otherwise honoured when no explicit
+    /// proxy is set
+    pub ignore_env_proxy: bool,
+    /// PEM encoded bundles of extra root certificates to trust
+    pub root_certificates: Vec<PathBuf>,
+    /// The `User-Agent` header to send, [DEFAULT_USER_AGENT] if unset
+    pub user_agent: Option<String>,
+    /// The local address to bind outgoing connections to
+    pub local_address: Option<IpAddr>,
+    /// The network interface to bind outgoing connections to (only supported
+    /// on Linux, Android and Fuchsia)
+    pub interface: Option<String>,
+}
+
+impl ClientConfig {
+    pub(crate) fn builder(&self) -> Result<ClientBuilder> {
+        let mut builder = ClientBuilder::new().user_agent(
+            self.user_agent
+                .as_deref()
+                .unwrap_or(DEFAULT_USER_AGENT)
+                .to_string(),
+        );
+
+        if let Some(timeout) = self.timeout.map(Duration::from_secs) {
+            builder = builder.timeout(timeout).connect_timeout(timeout);
+        }
+
+        if self.ignore_env_proxy {
+            builder = builder.no_proxy();
+        }
+        if let Some(ref proxy) = self.http_proxy {
+            builder = builder.proxy(Proxy::http(proxy)?);
+        }
+        if let Some(ref proxy) = self.https_proxy {
+            builder = builder.proxy(Proxy::https(proxy)?);
+        }
+        if let Some(ref proxy) = self.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+
+        for bundle in self.root_certificates.iter() {
+            trace!("adding root certificates from {}", bundle.display());
+            let pem = std::fs::read(bundle).map_err(|e| {
+                crate::Error::Request(format!(
+                    "cannot read root certificates from {}: {e}",
+                    bundle.display()
+                ))
+            })?;
+            for certificate in Certificate::from_pem_bundle(&pem)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        if let Some(address) = self.local_address {
+            builder = builder.local_address(address);
+        }
+
+        if let Some(ref interface) = self.interface {
+            cfg_interface(builder, interface)
+        } else {
+            Ok(builder)
+        }
+    }
+}
+
+#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+fn cfg_interface(builder: ClientBuilder, interface: &str) -> Result<ClientBuilder> {
+    Ok(builder.interface(interface))
+}
+
+#[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+fn cfg_interface(_: ClientBuilder, interface: &str) -> Result<ClientBuilder> {
+    Err(crate::Error::Request(format!(
+        "cannot bind to {interface}: interfaces are not supported on this platform"
+    )))
+}
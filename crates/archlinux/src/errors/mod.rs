@@ -21,9 +21,22 @@ pub enum Error {
         /// The status code returned by the server
         status_code: StatusCode,
     },
+    /// The mirror could not be rated over a protocol other than HTTP
+    #[error("could not probe {qualified_url:?} (from {url:?}): {reason}")]
+    Probe {
+        /// The address of the resource that was probed
+        qualified_url: String,
+        /// The URL of the particular mirror
+        url: String,
+        /// Why the server did not satisfy the probe
+        reason: String,
+    },
     #[error("could not build request {0}")]
     /// There was an error performing the request
     Request(String),
+    /// A timestamp was not a valid RFC 3339 date and time
+    #[error("invalid timestamp {0:?}")]
+    Timestamp(String),
     /// There was an error performing the request
     #[cfg(feature = "time")]
     #[error("could not parse time")]
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use crate::{Mirror, Protocol};
+
+/// Extracts the host from a mirror URL, without the scheme, credentials,
+/// port or path
+///
+/// # Example
+///
+/// ```rust
+/// # use mirrors_arch::url_host;
+/// assert_eq!(url_host("https://mirror.example.com:8443/archlinux/"), Some("mirror.example.com"));
+/// assert_eq!(url_host("rsync://[2001:db8::1]/archlinux/"), Some("[2001:db8::1]"));
+/// ```
+pub fn url_host(url: &str) -> Option<&str> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let authority = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = if authority.starts_with('[') {
+        authority.split_inclusive(']').next()?
+    } else {
+        authority.split(':').next()?
+    };
+    (!host.is_empty()).then_some(host)
+}
+
+impl Mirror {
+    /// The host serving this mirror, see [url_host]
+    pub fn host(&self) -> Option<&str> {
+        url_host(&self.url)
+    }
+}
+
+/// Groups items by the host of their URL. Groups are returned in the order
+/// their host first appears and items keep their relative order within a
+/// group. Items whose URL has no host are grouped by the full URL
+pub fn group_by_host<T, F>(items: impl IntoIterator<Item = T>, url: F) -> Vec<Vec<T>>
+where
+    F: for<'a> Fn(&'a T) -> &'a str,
+{
+    let mut hosts: HashMap<String, usize> = HashMap::new();
+    let mut groups: Vec<Vec<T>> = Vec::new();
+    for item in items {
+        let key = {
+            let url = url(&item);
+            url_host(url).unwrap_or(url).to_ascii_lowercase()
+        };
+        match hosts.get(&key) {
+            Some(&idx) => groups[idx].push(item),
+            None => {
+                hosts.insert(key, groups.len());
+                groups.push(vec![item]);
+            }
+        }
+    }
+    groups
+}
+
+/// Keeps a single item per host, choosing the one whose protocol comes first
+/// in `preference`. Protocols that are not in `preference` rank after those
+/// that are, and ties go to the item that appeared first
+///
+/// # Example
+///
+/// ```rust
+/// # use mirrors_arch::{one_per_host, Protocol};
+/// let urls = [
+///     ("http://mirror.example.com/archlinux/", Protocol::Http),
+///     ("https://mirror.example.com/archlinux/", Protocol::Https),
+///     ("http://other.example.com/archlinux/", Protocol::Http),
+/// ];
+/// let deduplicated = one_per_host(urls, &[Protocol::Https, Protocol::Http], |(url, protocol)| {
+///     (*url, *protocol)
+/// });
+/// assert_eq!(
+///     deduplicated,
+///     [
+///         ("https://mirror.example.com/archlinux/", Protocol::Https),
+///         ("http://other.example.com/archlinux/", Protocol::Http),
+///     ]
+/// );
+/// ```
+pub fn one_per_host<T, F>(
+    items: impl IntoIterator<Item = T>,
+    preference: &[Protocol],
+    endpoint: F,
+) -> Vec<T>
+where
+    F: for<'a> Fn(&'a T) -> (&'a str, Protocol),
+{
+    let rank = |item: &T| {
+        let (_, protocol) = endpoint(item);
+        preference
+            .iter()
+            .position(|preferred| *preferred == protocol)
+            .unwrap_or(preference.len())
+    };
+
+    group_by_host(items, |item| endpoint(item).0)
+        .into_iter()
+        .filter_map(|group| {
+            let best = group
+                .iter()
+                .enumerate()
+                .min_by_key(|(idx, item)| (rank(item), *idx))
+                .map(|(idx, _)| idx)?;
+            group.into_iter().nth(best)
+        })
+        .collect()
+}
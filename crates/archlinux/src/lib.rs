@@ -10,19 +10,29 @@ use std::time::{Duration, Instant};
 
 use futures::{future::BoxFuture, FutureExt};
 use log::{info, trace};
-use reqwest::{header::LOCATION, ClientBuilder, Response, StatusCode};
+use reqwest::{header::LOCATION, Response, StatusCode};
 
 use crate::response::external::Root;
 
 #[cfg(test)]
 mod test;
 
+mod client;
+pub use client::{ClientConfig, DEFAULT_USER_AGENT};
+
 mod errors;
 pub use errors::Error;
 
+mod hosts;
+pub use hosts::{group_by_host, one_per_host, url_host};
+
 pub use reqwest::Client;
 
+mod probe;
 mod response;
+mod timestamp;
+pub use timestamp::Timestamp;
+
 #[cfg(feature = "time")]
 #[cfg_attr(docsrs, doc(cfg(feature = "time")))]
 #[doc(no_inline)]
@@ -145,18 +155,43 @@ pub fn parse_local(contents: &str) -> Result<ArchLinux> {
 /// # }
 /// ```
 pub fn get_client(with_timeout: Option<u64>) -> Result<Client> {
-    let timeout = with_timeout.map(Duration::from_secs);
-
-    let mut client_builder = ClientBuilder::new();
-    if let Some(timeout) = timeout {
-        client_builder = client_builder.timeout(timeout).connect_timeout(timeout);
-    }
+    get_client_with_config(&ClientConfig {
+        timeout: with_timeout,
+        ..Default::default()
+    })
+}
 
-    Ok(client_builder.build()?)
+/// The same as [get_client] but applies the proxy, certificate, `User-Agent`
+/// and local address settings in a [ClientConfig]
+///
+/// # Example
+///
+/// ```rust
+/// # use mirrors_arch::{get_client_with_config, ClientConfig};
+/// # async fn foo()->Result<(), Box<dyn std::error::Error>>{
+/// let config = ClientConfig {
+///     proxy: Some(String::from("http://proxy.example.com:3128")),
+///     ..Default::default()
+/// };
+/// let client = get_client_with_config(&config)?;
+/// #  Ok(())
+/// # }
+/// ```
+pub fn get_client_with_config(config: &ClientConfig) -> Result<Client> {
+    Ok(config.builder()?.build()?)
 }
 
 /// Queries a mirrorlist and calculates how long it took to get a response
 ///
+/// `rsync://` mirrors are rated by timing the rsync daemon handshake up to the
+/// point where the mirror's module is accepted. `ftp://` mirrors are rated by
+/// timing an anonymous, passive mode download of the same file that is
+/// requested over HTTP up to its first byte, as HTTP mirrors are timed up to
+/// the response headers. All other mirrors are rated over HTTP.
+///
+/// The `rsync://` and `ftp://` probes do not go through `client` and time out
+/// after 10 seconds, see [rate_mirror_with_timeout] to change that.
+///
 /// # Parameters
 /// - `url` - The mirrorlist
 /// - `client` - The client returned from [get_client]
@@ -173,7 +208,43 @@ pub fn get_client(with_timeout: Option<u64>) -> Result<Client> {
 /// # }
 /// ```
 pub fn rate_mirror(url: String, client: Client) -> BoxFuture<'static, Result<(Duration, String)>> {
+    rate_mirror_with_timeout(url, client, None)
+}
+
+/// Like [rate_mirror], with a timeout for the `rsync://` and `ftp://` probes
+///
+/// # Parameters
+/// - `url` - The mirrorlist
+/// - `client` - The client returned from [get_client]
+/// - `with_timeout` - Connection timeout (in seconds) for the probes that do
+///   not go through `client`, usually the one `client` was built with. 10
+///   seconds if [None]
+///
+/// # Example
+///
+/// ```rust
+/// # use mirrors_arch::{get_client, rate_mirror_with_timeout};
+/// # async fn foo()->Result<(), Box<dyn std::error::Error>>{
+/// # let url = String::default();
+/// let timeout = Some(5);
+/// let client = get_client(timeout)?;
+/// let (duration, url) = rate_mirror_with_timeout(url, client, timeout).await?;
+/// #  Ok(())
+/// # }
+/// ```
+pub fn rate_mirror_with_timeout(
+    url: String,
+    client: Client,
+    with_timeout: Option<u64>,
+) -> BoxFuture<'static, Result<(Duration, String)>> {
     async move {
+        let timeout = with_timeout.map_or(probe::PROBE_TIMEOUT, Duration::from_secs);
+        if url.starts_with("rsync://") {
+            return probe::rsync::rate(url, timeout).await;
+        } else if url.starts_with("ftp://") {
+            return probe::ftp::rate(url, timeout).await;
+        }
+
         let uri = format!("{url}{FILE_PATH}");
 
         let now = Instant::now();
@@ -185,7 +256,7 @@ pub fn rate_mirror(url: String, client: Client) -> BoxFuture<'static, Result<(Du
         } else if response.status() == StatusCode::MOVED_PERMANENTLY {
             if let Some(new_uri) = response.headers().get(LOCATION) {
                 let new_url = String::from_utf8_lossy(new_uri.as_bytes()).replace(FILE_PATH, "");
-                rate_mirror(new_url.to_string(), client.clone()).await
+                rate_mirror_with_timeout(new_url.to_string(), client.clone(), with_timeout).await
             } else {
                 Err(Error::Rate {
                     qualified_url: uri,
@@ -220,7 +291,6 @@ pub fn rate_mirror(url: String, client: Client) -> BoxFuture<'static, Result<(Du
 /// #  Ok(())
 /// # }
 /// ```
-
 #[cfg(feature = "time")]
 #[cfg_attr(docsrs, doc(cfg(feature = "time")))]
 pub async fn get_last_sync(
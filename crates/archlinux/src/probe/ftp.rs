@@ -0,0 +1,217 @@
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use log::trace;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream,
+    },
+};
+
+use crate::{Result, FILE_PATH};
+
+use super::{probe_error, split_url};
+
+const FTP_PORT: u16 = 21;
+
+/// Logs in anonymously and starts downloading the probe file in passive mode,
+/// returning the time it took for the first byte to arrive. HTTP mirrors are
+/// timed up to the response headers, so neither includes the transfer itself
+pub(crate) async fn rate(url: String, timeout: Duration) -> Result<(Duration, String)> {
+    let (address, path) =
+        split_url(&url, FTP_PORT).ok_or_else(|| probe_error(&url, &url, "invalid ftp url"))?;
+    let file = format!("/{path}{FILE_PATH}");
+    let qualified_url = format!("ftp://{address}{file}");
+
+    let now = Instant::now();
+    match tokio::time::timeout(timeout, first_byte(&address, &file)).await {
+        Ok(Ok(at)) => {
+            trace!("first byte of {qualified_url} received");
+            Ok((at.duration_since(now), url))
+        }
+        Ok(Err(Session::Io(e))) => Err(probe_error(&qualified_url, &url, e.to_string())),
+        Ok(Err(Session::Reply { code, message })) => Err(probe_error(
+            &qualified_url,
+            &url,
+            format!("server replied {code} {message}"),
+        )),
+        Err(_) => Err(probe_error(&qualified_url, &url, "timed out")),
+    }
+}
+
+enum Session {
+    Io(std::io::Error),
+    Reply { code: u16, message: String },
+}
+
+impl From<std::io::Error> for Session {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+struct Control {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+    peer: SocketAddr,
+}
+
+impl Control {
+    async fn connect(address: &str) -> std::result::Result<Self, Session> {
+        trace!("connecting to ftp server at {address}");
+        let stream = TcpStream::connect(address).await?;
+        let peer = stream.peer_addr()?;
+        let (reader, writer) = stream.into_split();
+        Ok(Self {
+            reader: BufReader::new(reader),
+            writer,
+            peer,
+        })
+    }
+
+    /// Reads a (possibly multiline) reply from the server
+    async fn reply(&mut self) -> std::result::Result<(u16, String), Session> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line).await? == 0 {
+            return Err(closed());
+        }
+        let code = parse_code(&line)?;
+        if line.as_bytes().get(3) == Some(&b'-') {
+            let terminator = format!("{code} ");
+            loop {
+                line.clear();
+                if self.reader.read_line(&mut line).await? == 0 {
+                    return Err(closed());
+                }
+                if line.starts_with(&terminator) {
+                    break;
+                }
+            }
+        }
+        Ok((
+            code,
+            line.get(4..).unwrap_or_default().trim_end().to_string(),
+        ))
+    }
+
+    /// Reads a reply and fails unless its code is one of `expected`
+    async fn expect(&mut self, expected: &[u16]) -> std::result::Result<(u16, String), Session> {
+        let (code, message) = self.reply().await?;
+        if expected.contains(&code) {
+            Ok((code, message))
+        } else {
+            Err(Session::Reply { code, message })
+        }
+    }
+
+    /// Sends a command and fails unless the reply is one of `expected`
+    async fn command(
+        &mut self,
+        command: &str,
+        expected: &[u16],
+    ) -> std::result::Result<(u16, String), Session> {
+        self.writer
+            .write_all(format!("{command}\r\n").as_bytes())
+            .await?;
+        self.expect(expected).await
+    }
+}
+
+/// When the first byte of `file` arrived, or the transfer completed if it is
+/// empty. The rest of the transfer is abandoned
+async fn first_byte(address: &str, file: &str) -> std::result::Result<Instant, Session> {
+    let mut control = Control::connect(address).await?;
+    control.expect(&[220]).await?;
+
+    let (code, _) = control.command("USER anonymous", &[230, 331]).await?;
+    if code == 331 {
+        control.command("PASS anonymous@", &[230, 202]).await?;
+    }
+    control.command("TYPE I", &[200]).await?;
+
+    // not every server implements SIZE, but a missing file is still fatal
+    match control.command(&format!("SIZE {file}"), &[213]).await {
+        Err(Session::Reply { code: 550, message }) => {
+            return Err(Session::Reply { code: 550, message })
+        }
+        Err(Session::Io(e)) => return Err(Session::Io(e)),
+        _ => {}
+    }
+
+    let port = passive_port(&mut control).await?;
+    // connect to the control peer rather than the advertised address, which
+    // is frequently a private address behind NAT
+    let mut data = TcpStream::connect(SocketAddr::new(control.peer.ip(), port)).await?;
+
+    control
+        .command(&format!("RETR {file}"), &[125, 150])
+        .await?;
+    if data.read(&mut [0; 1]).await? == 0 {
+        trace!("{file} is empty");
+    }
+    let at = Instant::now();
+    drop(data);
+
+    let _ = control.writer.write_all(b"QUIT\r\n").await;
+
+    Ok(at)
+}
+
+/// Asks for a data port with `EPSV`, which also works over IPv6, falling back
+/// to `PASV` for servers that do not implement it
+async fn passive_port(control: &mut Control) -> std::result::Result<u16, Session> {
+    match control.command("EPSV", &[229]).await {
+        Ok((code, message)) => parse_epsv(&message).ok_or(Session::Reply { code, message }),
+        Err(Session::Reply { .. }) => {
+            let (code, message) = control.command("PASV", &[227]).await?;
+            parse_pasv(&message).ok_or(Session::Reply { code, message })
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn closed() -> Session {
+    Session::Io(std::io::Error::new(
+        std::io::ErrorKind::UnexpectedEof,
+        "ftp control connection closed",
+    ))
+}
+
+fn parse_code(line: &str) -> std::result::Result<u16, Session> {
+    line.get(..3)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| Session::Reply {
+            code: 0,
+            message: line.trim_end().to_string(),
+        })
+}
+
+/// Extracts the data port from a `229 Entering Extended Passive Mode (|||port|)` reply
+fn parse_epsv(message: &str) -> Option<u16> {
+    let start = message.find('(')?;
+    let end = message[start..].find(')')? + start;
+    let inner = &message[start + 1..end];
+    let delimiter = inner.chars().next()?;
+    match inner.split(delimiter).collect::<Vec<_>>().as_slice() {
+        ["", "", "", port, ""] => port.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Extracts the data port from a `227 Entering Passive Mode (h1,h2,h3,h4,p1,p2)` reply
+fn parse_pasv(message: &str) -> Option<u16> {
+    let start = message.find('(')?;
+    let end = message[start..].find(')')? + start;
+    let fields: Vec<u16> = message[start + 1..end]
+        .split(',')
+        .map(|f| f.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    match fields.as_slice() {
+        [_, _, _, _, high, low] if *high <= 255 && *low <= 255 => Some(high * 256 + low),
+        _ => None,
+    }
+}
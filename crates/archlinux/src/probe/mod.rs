@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+use crate::Error;
+
+pub(crate) mod ftp;
+pub(crate) mod rsync;
+
+/// Time allowed for a probe that does not go through a [Client](crate::Client),
+/// when no timeout is configured
+pub(crate) const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Splits a mirror URL into its host (with port) and path, dropping the scheme
+pub(crate) fn split_url(url: &str, default_port: u16) -> Option<(String, &str)> {
+    let (_, rest) = url.split_once("://")?;
+    let (authority, path) = match rest.split_once('/') {
+        Some((authority, path)) => (authority, path),
+        None => (rest, ""),
+    };
+    if authority.is_empty() {
+        return None;
+    }
+    let has_port = match authority.rsplit_once(':') {
+        Some((_, port)) => !authority.ends_with(']') && port.parse::<u16>().is_ok(),
+        None => false,
+    };
+    let address = if has_port {
+        authority.to_string()
+    } else {
+        format!("{authority}:{default_port}")
+    };
+    Some((address, path))
+}
+
+pub(crate) fn probe_error(qualified_url: &str, url: &str, reason: impl Into<String>) -> Error {
+    Error::Probe {
+        qualified_url: qualified_url.to_string(),
+        url: url.to_string(),
+        reason: reason.into(),
+    }
+}
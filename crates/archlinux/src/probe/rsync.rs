@@ -0,0 +1,91 @@
+use std::time::{Duration, Instant};
+
+use log::trace;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+
+use crate::Result;
+
+use super::{probe_error, split_url};
+
+const RSYNC_PORT: u16 = 873;
+const GREETING: &str = "@RSYNCD: ";
+const CLIENT_VERSION: &str = "@RSYNCD: 31.0\n";
+
+/// Speaks the rsync daemon handshake and requests the mirror's module,
+/// returning the time it took for the daemon to accept it
+pub(crate) async fn rate(url: String, timeout: Duration) -> Result<(Duration, String)> {
+    let (address, path) =
+        split_url(&url, RSYNC_PORT).ok_or_else(|| probe_error(&url, &url, "invalid rsync url"))?;
+    let module = path.split('/').next().unwrap_or_default().to_string();
+    let qualified_url = format!("rsync://{address}/{module}");
+    if module.is_empty() {
+        return Err(probe_error(&qualified_url, &url, "no rsync module in url"));
+    }
+
+    let now = Instant::now();
+    match tokio::time::timeout(timeout, handshake(&address, &module)).await {
+        Ok(Ok(())) => Ok((now.elapsed(), url)),
+        Ok(Err(Handshake::Io(e))) => Err(probe_error(&qualified_url, &url, e.to_string())),
+        Ok(Err(Handshake::Refused(reason))) => Err(probe_error(&qualified_url, &url, reason)),
+        Err(_) => Err(probe_error(&qualified_url, &url, "timed out")),
+    }
+}
+
+enum Handshake {
+    Io(std::io::Error),
+    Refused(String),
+}
+
+impl From<std::io::Error> for Handshake {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+async fn handshake(address: &str, module: &str) -> std::result::Result<(), Handshake> {
+    trace!("connecting to rsync daemon at {address}");
+    let stream = TcpStream::connect(address).await?;
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut line = String::new();
+
+    reader.read_line(&mut line).await?;
+    if !line.starts_with(GREETING) {
+        return Err(Handshake::Refused(format!(
+            "unexpected greeting {:?}",
+            line.trim_end()
+        )));
+    }
+
+    writer.write_all(CLIENT_VERSION.as_bytes()).await?;
+    writer.write_all(format!("{module}\n").as_bytes()).await?;
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(Handshake::Refused(String::from(
+                "connection closed before module was accepted",
+            )));
+        }
+        let response = line.trim_end();
+        if response == "@RSYNCD: OK" {
+            // the server now expects transfer arguments, hang up instead
+            let _ = writer.shutdown().await;
+            return Ok(());
+        } else if response.starts_with("@RSYNCD: AUTHREQD") {
+            return Err(Handshake::Refused(format!(
+                "module {module:?} requires authentication"
+            )));
+        } else if let Some(error) = response.strip_prefix("@ERROR") {
+            return Err(Handshake::Refused(
+                error.trim_start_matches(':').trim().to_string(),
+            ));
+        } else if response.starts_with("@RSYNCD: EXIT") {
+            return Err(Handshake::Refused(format!("module {module:?} unavailable")));
+        }
+        // anything else is the server's message of the day
+    }
+}
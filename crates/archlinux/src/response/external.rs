@@ -2,6 +2,9 @@ use std::fmt::Display;
 
 #[cfg(feature = "time")]
 use chrono::{DateTime, Utc};
+
+#[cfg(not(feature = "time"))]
+use crate::Timestamp;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
@@ -10,7 +13,7 @@ pub(crate) struct Root {
     #[cfg(feature = "time")]
     pub last_check: DateTime<Utc>,
     #[cfg(not(feature = "time"))]
-    pub last_check: String,
+    pub last_check: Timestamp,
     pub num_checks: u8,
     pub check_frequency: u16,
     pub urls: Vec<Url>,
@@ -24,7 +27,7 @@ pub(crate) struct Url {
     #[cfg(feature = "time")]
     pub last_sync: Option<DateTime<Utc>>,
     #[cfg(not(feature = "time"))]
-    pub last_sync: Option<String>,
+    pub last_sync: Option<Timestamp>,
     pub completion_pct: f32,
     pub delay: Option<i64>,
     pub duration_avg: Option<f64>,
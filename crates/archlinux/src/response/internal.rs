@@ -1,3 +1,5 @@
+use std::time::{Duration, SystemTime};
+
 use itertools::Itertools;
 use log::debug;
 use serde::Deserialize;
@@ -5,6 +7,9 @@ use serde::Deserialize;
 #[cfg(feature = "time")]
 use chrono::{DateTime, Utc};
 
+#[cfg(not(feature = "time"))]
+use crate::Timestamp;
+
 use super::external::{Protocol, Root};
 
 #[derive(Debug, Clone, PartialEq, Deserialize)]
@@ -17,7 +22,7 @@ pub struct ArchLinux {
     pub last_check: DateTime<Utc>,
     #[cfg(not(feature = "time"))]
     /// Last successful check for mirrorlists
-    pub last_check: String,
+    pub last_check: Timestamp,
     /// Number of checks as returned by the server
     pub num_checks: u8,
     /// Check frequency as returned by the server
@@ -59,7 +64,7 @@ pub struct Mirror {
     pub last_sync: Option<DateTime<Utc>>,
     #[cfg(not(feature = "time"))]
     /// Time when the last successful synchronisation occurred
-    pub last_sync: Option<String>,
+    pub last_sync: Option<Timestamp>,
     /// ipv4 enabled
     pub ipv4: bool,
     /// ipv6 enabled
@@ -68,6 +73,40 @@ pub struct Mirror {
     pub isos: bool,
 }
 
+impl Mirror {
+    /// How long before `now` the mirror last synchronised, or [None] if it
+    /// has never synchronised. Synchronisations later than `now` have an age
+    /// of zero
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::time::{Duration, SystemTime};
+    /// # use mirrors_arch::parse_local;
+    /// # fn foo() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let json = std::fs::read_to_string("archmirrors.json")?;
+    /// let mirrors = parse_local(&json)?;
+    /// let now = SystemTime::now();
+    /// let recent = mirrors
+    ///     .countries
+    ///     .iter()
+    ///     .flat_map(|country| country.mirrors.iter())
+    ///     .filter(|mirror| {
+    ///         mirror
+    ///             .sync_age(now)
+    ///             .is_some_and(|age| age <= Duration::from_secs(24 * 3600))
+    ///     });
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn sync_age(&self, now: SystemTime) -> Option<Duration> {
+        self.last_sync.map(|last_sync| {
+            now.duration_since(SystemTime::from(last_sync))
+                .unwrap_or_default()
+        })
+    }
+}
+
 impl From<Root> for ArchLinux {
     fn from(mut raw: Root) -> Self {
         debug!("minifying mirrors");
@@ -96,10 +135,7 @@ impl From<Root> for ArchLinux {
                             delay: f.delay,
                             score: f.score,
                             duration_stddev: f.duration_stddev,
-                            #[cfg(feature = "time")]
                             last_sync: f.last_sync,
-                            #[cfg(not(feature = "time"))]
-                            last_sync: f.last_sync.clone(),
                             ipv4: f.ipv4,
                             ipv6: f.ipv6,
                             isos: f.isos,
@@ -104,3 +104,245 @@ async fn rate_mirror() -> Result<()> {
     assert!(res.is_ok());
     Ok(())
 }
+
+async fn fake_rsync_daemon(reply: &'static str) -> std::io::Result<String> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    tokio::spawn(async move {
+        if let Ok((mut stream, _)) = listener.accept().await {
+            let _ = stream.write_all(b"@RSYNCD: 31.0\n").await;
+            let mut buf = [0; 64];
+            let _ = stream.read(&mut buf).await;
+            let _ = stream.write_all(reply.as_bytes()).await;
+        }
+    });
+    Ok(format!("rsync://{address}/archlinux/"))
+}
+
+#[tokio::test]
+async fn rate_rsync_mirror() -> Result<()> {
+    let url = fake_rsync_daemon("welcome to the mirror\n@RSYNCD: OK\n")
+        .await
+        .unwrap();
+
+    let (_, rated) = crate::rate_mirror(url.clone(), get_client(None)?).await?;
+    assert_eq!(rated, url);
+    Ok(())
+}
+
+/// The address of a port nothing is listening on
+async fn closed_port() -> std::io::Result<std::net::SocketAddr> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    listener.local_addr()
+}
+
+#[tokio::test]
+async fn rate_rsync_mirror_unreachable() -> Result<()> {
+    let url = format!("rsync://{}/archlinux/", closed_port().await.unwrap());
+
+    let res = crate::rate_mirror(url.clone(), get_client(None)?).await;
+    assert!(matches!(res, Err(crate::Error::Probe { url: ref probed, .. }) if *probed == url));
+    Ok(())
+}
+
+#[tokio::test]
+async fn rate_rsync_mirror_unknown_module() -> Result<()> {
+    let url = fake_rsync_daemon("@ERROR: Unknown module 'archlinux'\n")
+        .await
+        .unwrap();
+
+    let res = crate::rate_mirror(url, get_client(None)?).await;
+    assert!(matches!(res, Err(crate::Error::Probe { .. })));
+    Ok(())
+}
+
+async fn fake_ftp_server(has_file: bool, epsv: bool) -> std::io::Result<String> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    tokio::spawn(async move {
+        let Ok((stream, _)) = listener.accept().await else {
+            return;
+        };
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        let _ = writer.write_all(b"220-welcome\r\n220 ready\r\n").await;
+        let mut data = None;
+        let mut line = String::new();
+        while reader.read_line(&mut line).await.unwrap_or_default() > 0 {
+            let reply = match line.split_whitespace().next().unwrap_or_default() {
+                "USER" => "331 password please\r\n".to_string(),
+                "PASS" => "230 logged in\r\n".to_string(),
+                "TYPE" => "200 binary\r\n".to_string(),
+                "SIZE" if has_file => "213 5\r\n".to_string(),
+                "SIZE" => "550 no such file\r\n".to_string(),
+                "EPSV" if !epsv => "502 not implemented\r\n".to_string(),
+                command @ ("EPSV" | "PASV") => {
+                    let Ok(listener) = tokio::net::TcpListener::bind("127.0.0.1:0").await else {
+                        return;
+                    };
+                    let port = listener.local_addr().map(|a| a.port()).unwrap_or_default();
+                    data = Some(listener);
+                    if command == "EPSV" {
+                        format!("229 Entering Extended Passive Mode (|||{port}|)\r\n")
+                    } else {
+                        format!(
+                            "227 Entering Passive Mode (127,0,0,1,{},{})\r\n",
+                            port / 256,
+                            port % 256
+                        )
+                    }
+                }
+                "RETR" => {
+                    let _ = writer.write_all(b"150 opening data connection\r\n").await;
+                    if let Some(listener) = data.take() {
+                        if let Ok((mut stream, _)) = listener.accept().await {
+                            let _ = stream.write_all(b"hello").await;
+                        }
+                    }
+                    "226 transfer complete\r\n".to_string()
+                }
+                _ => "221 bye\r\n".to_string(),
+            };
+            let _ = writer.write_all(reply.as_bytes()).await;
+            line.clear();
+        }
+    });
+    Ok(format!("ftp://{address}/archlinux/"))
+}
+
+#[tokio::test]
+async fn rate_ftp_mirror() -> Result<()> {
+    let url = fake_ftp_server(true, true).await.unwrap();
+
+    let (_, rated) = crate::rate_mirror(url.clone(), get_client(None)?).await?;
+    assert_eq!(rated, url);
+    Ok(())
+}
+
+#[tokio::test]
+async fn rate_ftp_mirror_without_epsv() -> Result<()> {
+    let url = fake_ftp_server(true, false).await.unwrap();
+
+    let (_, rated) = crate::rate_mirror(url.clone(), get_client(None)?).await?;
+    assert_eq!(rated, url);
+    Ok(())
+}
+
+#[tokio::test]
+async fn rate_ftp_mirror_unreachable() -> Result<()> {
+    let url = format!("ftp://{}/archlinux/", closed_port().await.unwrap());
+
+    let res = crate::rate_mirror(url.clone(), get_client(None)?).await;
+    assert!(matches!(res, Err(crate::Error::Probe { url: ref probed, .. }) if *probed == url));
+    Ok(())
+}
+
+#[tokio::test]
+async fn rate_ftp_mirror_missing_file() -> Result<()> {
+    let url = fake_ftp_server(false, true).await.unwrap();
+
+    let res = crate::rate_mirror(url, get_client(None)?).await;
+    assert!(matches!(res, Err(crate::Error::Probe { .. })));
+    Ok(())
+}
+
+#[test]
+fn parse_timestamps() -> Result<()> {
+    use crate::Timestamp;
+
+    let utc: Timestamp = "2022-12-19T14:19:52.677Z".parse()?;
+    assert_eq!(utc.unix_seconds(), 1671459592);
+    assert_eq!(utc.to_string(), "2022-12-19T14:19:52.677Z");
+
+    let offset: Timestamp = "2022-12-19T16:19:52.677+02:00".parse()?;
+    assert_eq!(offset, utc);
+
+    let leap: Timestamp = "2024-02-29T00:00:00Z".parse()?;
+    assert_eq!(leap.to_string(), "2024-02-29T00:00:00Z");
+    assert_eq!(Timestamp::from(leap.to_system_time()), leap);
+
+    assert!("2023-02-29T00:00:00Z".parse::<Timestamp>().is_err());
+    assert!("2022-12-19 14:19".parse::<Timestamp>().is_err());
+    Ok(())
+}
+
+#[test]
+fn mirror_sync_age() -> Result<()> {
+    let mirrors = crate::parse_local(LOCAL_SOURCE)?;
+    let mirror = mirrors
+        .countries
+        .iter()
+        .flat_map(|country| country.mirrors.iter())
+        .find(|mirror| mirror.url == "https://mirror.aarnet.edu.au/pub/archlinux/")
+        .expect("sample contains mirror");
+
+    // last_sync is 2022-12-19T13:23:01Z
+    let now = crate::Timestamp::from_unix_seconds(1671459781).to_system_time();
+    assert_eq!(
+        mirror.sync_age(now),
+        Some(std::time::Duration::from_secs(3600))
+    );
+    Ok(())
+}
+
+#[test]
+fn client_config_errors() {
+    use crate::{get_client_with_config, ClientConfig};
+
+    let proxy = ClientConfig {
+        proxy: Some(String::from("::not a url")),
+        ..Default::default()
+    };
+    assert!(get_client_with_config(&proxy).is_err());
+
+    let certificates = ClientConfig {
+        root_certificates: vec![std::path::PathBuf::from("/nonexistent/mirrors-arch/ca.pem")],
+        ..Default::default()
+    };
+    assert!(matches!(
+        get_client_with_config(&certificates),
+        Err(crate::Error::Request(_))
+    ));
+}
+
+#[tokio::test]
+async fn client_config_user_agent() -> Result<()> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::{get_client_with_config, ClientConfig};
+
+    // replies with the request it received
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        if let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = [0; 1024];
+            let read = stream.read(&mut buf).await.unwrap_or_default();
+            let reply =
+                format!("HTTP/1.1 200 OK\r\nContent-Length: {read}\r\nConnection: close\r\n\r\n");
+            let _ = stream.write_all(reply.as_bytes()).await;
+            let _ = stream.write_all(&buf[..read]).await;
+        }
+    });
+
+    let client = get_client_with_config(&ClientConfig {
+        user_agent: Some(String::from("my-app/1.0")),
+        // the request must reach the server above, whatever the environment
+        ignore_env_proxy: true,
+        ..Default::default()
+    })?;
+    let request = client
+        .get(format!("http://{address}/"))
+        .send()
+        .await?
+        .text()
+        .await?;
+    assert!(request
+        .lines()
+        .any(|f| f.eq_ignore_ascii_case("user-agent: my-app/1.0")));
+    Ok(())
+}
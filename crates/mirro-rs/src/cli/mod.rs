@@ -1,130 +1,723 @@
-use std::path::PathBuf;
+use std::{fmt::Display, net::IpAddr, path::PathBuf, str::FromStr};
 
-use clap::{Parser, ValueEnum};
-use serde::Deserialize;
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
 
 pub const DEFAULT_MIRROR_COUNT: u16 = 50;
 pub const DEFAULT_CACHE_TTL: u16 = 24;
 pub const ARCH_URL: &str = "https://archlinux.org/mirrors/status/json/";
 
-#[cfg_attr(test, derive(Default))]
-#[derive(Parser, Debug, Deserialize)]
+#[derive(Parser, Debug, Default, Clone, Deserialize, Serialize)]
 #[command(author, version, about, long_about = None)]
 pub struct ArgConfig {
+    #[command(subcommand)]
+    #[serde(skip)]
+    pub command: Option<Command>,
     #[command(flatten)]
+    #[serde(default)]
     pub general: Args,
     #[command(flatten)]
+    #[serde(default)]
     pub filters: Filters,
 }
 
-#[cfg_attr(test, derive(Default))]
-#[derive(clap::Args, Debug, Deserialize)]
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Browse mirrors and export a selection interactively (default)
+    Tui,
+    /// Export the mirrorlist without starting the TUI (same as --direct)
+    Export,
+    /// Print the mirrors that pass the filters
+    List(ListArgs),
+    /// Rate mirrors by download speed
+    Rate {
+        /// Mirror URLs to rate
+        #[arg(required = true)]
+        urls: Vec<String>,
+    },
+    /// Show the state of the cached mirrorlist and its source
+    Status,
+    /// Manage the cached mirrorlist
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
+    },
+    /// List the backups of the outfile
+    Backups,
+    /// Restore the outfile from a backup
+    Rollback {
+        /// Timestamp (or the start of one) of the backup to restore [default: the newest]
+        #[arg(long)]
+        to: Option<String>,
+    },
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+    /// Accept reflector's options (also used when mirro-rs is invoked as `reflector`)
+    Reflector {
+        /// reflector options, such as --latest 20 --protocol https --sort rate --save <file>
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+}
+
+#[derive(clap::Args, Debug, Clone, PartialEq, Eq)]
+pub struct ListArgs {
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = ListFormat::Table)]
+    pub format: ListFormat,
+
+    /// Columns to print
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [Column::Code, Column::Protocol, Column::Completion, Column::Delay, Column::Score, Column::Url]
+    )]
+    pub columns: Vec<Column>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, ValueEnum)]
+pub enum ListFormat {
+    Table,
+    Json,
+    Csv,
+    Ndjson,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, ValueEnum)]
+pub enum Column {
+    Url,
+    Host,
+    Country,
+    Code,
+    Protocol,
+    Completion,
+    Delay,
+    Duration,
+    Score,
+    LastSync,
+    Ipv4,
+    Ipv6,
+    Isos,
+}
+
+#[derive(Subcommand, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheCommand {
+    /// Delete the cached mirrorlist
+    Clear,
+    /// Print the location, age and contents of the cached mirrorlist
+    Show,
+}
+
+#[derive(Subcommand, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigCommand {
+    /// Validate the command line and the configuration file, then exit
+    Check,
+    /// Print the value of every setting and where it was set: on the command
+    /// line, in a MIRRO_RS_* environment variable, in a profile, in the
+    /// configuration file or by default
+    Show {
+        /// Print the effective configuration in this format instead, ready to
+        /// be used as a configuration file
+        #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+        #[arg(long, value_enum)]
+        format: Option<ConfigFormat>,
+    },
+    /// Write a commented configuration file with the default settings to
+    /// $XDG_CONFIG_HOME/mirro-rs, keeping any existing file unless --force is
+    /// given
+    #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+    Init {
+        /// Format of the file [default: toml, json or yaml, whichever is
+        /// supported first]
+        #[arg(long, value_enum)]
+        format: Option<ConfigFormat>,
+    },
+}
+
+/// The supported configuration file formats, in the order they are looked for
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, ValueEnum, Default)]
+pub enum ConfigFormat {
+    #[cfg(feature = "toml")]
+    #[cfg_attr(feature = "toml", default)]
+    Toml,
+    #[cfg(feature = "json")]
+    #[cfg_attr(all(feature = "json", not(feature = "toml")), default)]
+    Json,
+    #[cfg(feature = "yaml")]
+    #[cfg_attr(not(any(feature = "json", feature = "toml")), default)]
+    Yaml,
+}
+
+#[derive(clap::Args, Debug, Default, Clone, Deserialize, Serialize)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
     /// File to write mirrors to
-    #[arg(short, long)]
+    #[arg(short, long, env = "MIRRO_RS_OUTFILE", global = true)]
     pub outfile: Option<PathBuf>,
 
     /// Number of mirrors to export [default: 50]
-    #[arg(short, long)]
-    #[serde(default = "default_export")]
+    #[arg(
+        short,
+        long,
+        visible_alias = "number",
+        env = "MIRRO_RS_EXPORT",
+        global = true
+    )]
+    #[serde(default = "default_export", alias = "number")]
     pub export: Option<u16>,
 
+    /// Keep the n most recently synced mirrors
+    #[arg(long, value_name = "n", env = "MIRRO_RS_LATEST", global = true)]
+    pub latest: Option<u16>,
+
+    /// Then keep the n mirrors with the best score
+    #[arg(long, value_name = "n", env = "MIRRO_RS_SCORE", global = true)]
+    pub score: Option<u16>,
+
+    /// Then rate the remaining mirrors and keep the n fastest
+    #[arg(long, value_name = "n", env = "MIRRO_RS_FASTEST", global = true)]
+    pub fastest: Option<u16>,
+
+    /// Export at most n mirrors from any one country
+    #[arg(
+        long,
+        value_name = "n",
+        env = "MIRRO_RS_MAX_PER_COUNTRY",
+        global = true
+    )]
+    #[serde(rename = "max-per-country")]
+    pub max_per_country: Option<u16>,
+
+    /// Swap in the best mirrors of other countries until the export spans at
+    /// least n countries
+    #[arg(long, value_name = "n", env = "MIRRO_RS_MIN_COUNTRIES", global = true)]
+    #[serde(rename = "min-countries")]
+    pub min_countries: Option<u16>,
+
     /// An order to view all countries
-    #[arg(short, long, value_enum)]
+    #[arg(short, long, value_enum, env = "MIRRO_RS_VIEW", global = true)]
     #[serde(default = "view")]
     pub view: Option<ViewSort>,
 
-    /// Default sort for exported mirrors
-    #[arg(short, long, value_enum)]
+    /// Default sort for exported mirrors: comma separated keys (completion,
+    /// delay, duration, score), each prefixed with - to sort in descending
+    /// order, such as score,delay,-completion
+    #[arg(
+        short,
+        long,
+        value_name = "keys",
+        env = "MIRRO_RS_SORT",
+        allow_hyphen_values = true,
+        global = true
+    )]
     #[serde(default = "sort")]
-    pub sort: Option<SelectionSort>,
+    pub sort: Option<SortSpec>,
 
     /// Number of hours to cache mirrorlist for
-    #[arg(short, long)]
+    #[arg(short, long, env = "MIRRO_RS_CACHE_TTL", global = true)]
     #[serde(rename = "cache-ttl")]
     #[serde(default = "default_ttl")]
     pub ttl: Option<u16>,
 
     /// URL to check for mirrors
-    #[arg(short, long)]
+    #[arg(short, long, env = "MIRRO_RS_URL", global = true)]
     #[serde(default = "url")]
     pub url: Option<String>,
 
     /// Specify alternate configuration file
-    #[arg(long)]
+    #[arg(long, env = "MIRRO_RS_CONFIG", global = true)]
     #[serde(skip)]
     #[cfg(any(feature = "toml", feature = "yaml", feature = "json"))]
     pub config: Option<PathBuf>,
 
+    /// Use a profile from the configuration file, instead of the one picked
+    /// for this host
+    #[arg(long, value_name = "name", env = "MIRRO_RS_PROFILE", global = true)]
+    #[serde(skip)]
+    #[cfg(any(feature = "toml", feature = "yaml", feature = "json"))]
+    pub profile: Option<String>,
+
     /// Sort mirrorlists by download speed when exporting
-    #[arg(short, long)]
-    #[serde(default, rename = "rate-speed")]
-    pub rate: bool,
+    #[arg(
+        short,
+        long,
+        env = "MIRRO_RS_RATE_SPEED",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = clap::builder::BoolishValueParser::new(),
+        global = true
+    )]
+    #[serde(default = "disable", rename = "rate-speed")]
+    pub rate: Option<bool>,
+
+    /// Do not sort mirrorlists by download speed, even if the configuration
+    /// file says to
+    #[arg(long, global = true)]
+    #[serde(skip)]
+    pub no_rate: bool,
 
     /// Connection timeout in seconds
-    #[arg(long = "timeout")]
+    #[arg(long = "timeout", env = "MIRRO_RS_TIMEOUT", global = true)]
     pub timeout: Option<u64>,
 
     /// Extra CDNs to check for mirrors
-    #[arg(short, long)]
+    #[arg(
+        short,
+        long,
+        env = "MIRRO_RS_INCLUDE",
+        value_delimiter = ',',
+        global = true
+    )]
     pub include: Option<Vec<String>>,
 
     /// Skip TUI session and directly export the mirrorlist
-    #[arg(short, long)]
-    #[serde(default)]
-    pub direct: bool,
+    #[arg(
+        short,
+        long,
+        env = "MIRRO_RS_DIRECT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = clap::builder::BoolishValueParser::new(),
+        global = true
+    )]
+    #[serde(default = "disable")]
+    pub direct: Option<bool>,
+
+    /// Open the TUI session, even if the configuration file says to export
+    /// directly
+    #[arg(long, global = true)]
+    #[serde(skip)]
+    pub no_direct: bool,
+
+    /// Number of backups of the outfile to keep, 0 disables them [default: 5]
+    #[arg(long, env = "MIRRO_RS_BACKUPS", global = true)]
+    pub backups: Option<u16>,
+
+    /// Where backups of the outfile are kept [default: $XDG_DATA_HOME/mirro-rs/backups]
+    #[arg(long, env = "MIRRO_RS_BACKUP_DIR", global = true)]
+    #[serde(rename = "backup-dir")]
+    pub backup_dir: Option<PathBuf>,
+
+    /// Where mirro-rs' block goes relative to your own lines, when they are all on one side of it [default: after]
+    #[arg(long, value_enum, env = "MIRRO_RS_PLACEMENT", global = true)]
+    pub placement: Option<Placement>,
+
+    /// Leave out the header describing how the mirrorlist was generated
+    #[arg(
+        long,
+        env = "MIRRO_RS_NO_HEADER",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = clap::builder::BoolishValueParser::new(),
+        global = true
+    )]
+    #[serde(default = "disable", rename = "no-header")]
+    pub no_header: Option<bool>,
+
+    /// Keep the header, even if the configuration file leaves it out
+    #[arg(long, global = true)]
+    #[serde(skip)]
+    pub header: bool,
+
+    /// Leave out the comment describing each server
+    #[arg(
+        long,
+        env = "MIRRO_RS_NO_COMMENTS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = clap::builder::BoolishValueParser::new(),
+        global = true
+    )]
+    #[serde(default = "disable", rename = "no-comments")]
+    pub no_comments: Option<bool>,
+
+    /// Keep the comment describing each server, even if the configuration file leaves it out
+    #[arg(long, global = true)]
+    #[serde(skip)]
+    pub comments: bool,
+
+    /// Write the outfile even if no mirrors were selected, and let config init
+    /// replace an existing configuration file
+    #[arg(long, env = "MIRRO_RS_FORCE", global = true)]
+    #[serde(skip)]
+    pub force: bool,
+
+    /// Fail if any mirror could not be rated or the outfile could not be backed up
+    #[arg(
+        long,
+        env = "MIRRO_RS_STRICT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = clap::builder::BoolishValueParser::new(),
+        global = true
+    )]
+    #[serde(default = "disable")]
+    pub strict: Option<bool>,
+
+    /// Only warn about mirrors that could not be rated, even if the configuration file says to fail
+    #[arg(long, global = true)]
+    #[serde(skip)]
+    pub no_strict: bool,
+
+    /// Print the mirrorlist to stdout instead of writing it to the outfile
+    #[arg(long, env = "MIRRO_RS_DRY_RUN", global = true)]
+    #[serde(skip)]
+    pub dry_run: bool,
+
+    /// Compare the mirrorlist with the current contents of the outfile
+    #[arg(long, env = "MIRRO_RS_DIFF", global = true)]
+    #[serde(skip)]
+    pub diff: bool,
+
+    /// Keep a single URL per mirror host when exporting
+    #[arg(
+        long,
+        env = "MIRRO_RS_ONE_PER_HOST",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = clap::builder::BoolishValueParser::new(),
+        global = true
+    )]
+    #[serde(default = "disable", rename = "one-per-host")]
+    pub one_per_host: Option<bool>,
+
+    /// Keep every URL of a mirror host, even if the configuration file says to keep one
+    #[arg(long, global = true)]
+    #[serde(skip)]
+    pub no_one_per_host: bool,
+
+    /// Protocol preference when keeping one URL per host [default: https,http,ftp,rsync]
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        env = "MIRRO_RS_PREFER",
+        global = true
+    )]
+    pub prefer: Option<Vec<Protocol>>,
+
+    /// Proxy for all requests (http, https or socks5)
+    #[arg(long, env = "MIRRO_RS_PROXY", global = true)]
+    pub proxy: Option<String>,
+
+    /// Proxy for http requests
+    #[arg(long, env = "MIRRO_RS_HTTP_PROXY", global = true)]
+    #[serde(rename = "http-proxy")]
+    pub http_proxy: Option<String>,
+
+    /// Proxy for https requests
+    #[arg(long, env = "MIRRO_RS_HTTPS_PROXY", global = true)]
+    #[serde(rename = "https-proxy")]
+    pub https_proxy: Option<String>,
+
+    /// Ignore proxies set through HTTP_PROXY, HTTPS_PROXY, ALL_PROXY and NO_PROXY
+    #[arg(
+        long,
+        env = "MIRRO_RS_NO_ENV_PROXY",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = clap::builder::BoolishValueParser::new(),
+        global = true
+    )]
+    #[serde(default = "disable", rename = "no-env-proxy")]
+    pub no_env_proxy: Option<bool>,
+
+    /// Use the proxies set through the environment, even if the configuration file says to ignore them
+    #[arg(long, global = true)]
+    #[serde(skip)]
+    pub env_proxy: bool,
+
+    /// Extra root certificate bundles (PEM) to trust
+    #[arg(
+        long = "ca-cert",
+        env = "MIRRO_RS_CA_CERTS",
+        value_delimiter = ',',
+        global = true
+    )]
+    #[serde(rename = "ca-certs")]
+    pub ca_certs: Option<Vec<PathBuf>>,
+
+    /// User-Agent sent with requests [default: mirro-rs/<version>]
+    #[arg(long, env = "MIRRO_RS_USER_AGENT", global = true)]
+    #[serde(rename = "user-agent")]
+    pub user_agent: Option<String>,
+
+    /// Local address to bind connections to
+    #[arg(long, env = "MIRRO_RS_BIND_ADDRESS", global = true)]
+    #[serde(rename = "bind-address")]
+    pub bind_address: Option<IpAddr>,
+
+    /// Network interface to bind connections to
+    #[arg(long, env = "MIRRO_RS_INTERFACE", global = true)]
+    pub interface: Option<String>,
 }
 
-#[cfg_attr(test, derive(Default))]
-#[derive(clap::Args, Debug, Clone, Eq, PartialEq, Deserialize)]
+#[derive(clap::Args, Debug, Default, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub struct Filters {
     /// How old (in hours) should the mirrors be since last synchronisation
-    #[arg(long, short)]
+    #[arg(long, short, env = "MIRRO_RS_AGE", global = true)]
     pub age: Option<u16>,
 
     /// Countries to search for mirrorlists
-    #[arg(short)]
+    #[arg(
+        short,
+        env = "MIRRO_RS_COUNTRIES",
+        value_delimiter = ',',
+        global = true
+    )]
     #[serde(rename = "countries")]
     #[serde(default)]
     pub country: Option<Vec<String>>,
 
     /// Filters to use on mirrorlists
-    #[arg(short, long, value_enum)]
+    #[arg(
+        short,
+        long,
+        value_enum,
+        env = "MIRRO_RS_PROTOCOLS",
+        value_delimiter = ',',
+        global = true
+    )]
     #[serde(default = "filters")]
     pub protocols: Option<Vec<Protocol>>,
 
-    ///Only return mirrors that support IPv4.
-    #[arg(long)]
-    #[serde(default = "enable")]
-    pub ipv4: bool,
-    ///Only return mirrors that support IPv6.
-    #[arg(long)]
-    #[serde(default = "enable")]
-    pub ipv6: bool,
-    /// Only return mirrors that host ISOs.
-    #[arg(long)]
-    #[serde(default = "enable")]
-    pub isos: bool,
+    /// Only return mirrors that support IPv4, or with =exclude only those that do not.
+    #[arg(
+        long,
+        env = "MIRRO_RS_IPV4",
+        value_name = "exclude",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        global = true
+    )]
+    #[serde(default = "required")]
+    pub ipv4: Option<Requirement>,
+    /// Return mirrors whether they support IPv4 or not.
+    #[arg(long, global = true)]
+    #[serde(skip)]
+    pub no_ipv4: bool,
+    /// Only return mirrors that support IPv6, or with =exclude only those that do not.
+    #[arg(
+        long,
+        env = "MIRRO_RS_IPV6",
+        value_name = "exclude",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        global = true
+    )]
+    #[serde(default = "required")]
+    pub ipv6: Option<Requirement>,
+    /// Return mirrors whether they support IPv6 or not.
+    #[arg(long, global = true)]
+    #[serde(skip)]
+    pub no_ipv6: bool,
+    /// Only return mirrors that host ISOs, or with =exclude only those that do not.
+    #[arg(
+        long,
+        env = "MIRRO_RS_ISOS",
+        value_name = "exclude",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        global = true
+    )]
+    #[serde(default = "required")]
+    pub isos: Option<Requirement>,
+    /// Return mirrors whether they host ISOs or not.
+    #[arg(long, global = true)]
+    #[serde(skip)]
+    pub no_isos: bool,
 
     /// Set the minimum completion percent for the returned mirrors.
-    #[arg(long)]
+    #[arg(long, env = "MIRRO_RS_COMPLETION_PERCENT", global = true)]
     #[serde(default = "completion", rename = "completion-percent")]
     pub completion_percent: Option<u8>,
+
+    /// Drop mirrors whose URL or host matches a glob (or a regex prefixed with `re:`)
+    #[arg(long, value_name = "PATTERN", env = "MIRRO_RS_EXCLUDE", global = true)]
+    pub exclude: Option<Vec<String>>,
+
+    /// Only keep mirrors whose URL or host matches a glob (or a regex prefixed with `re:`)
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        env = "MIRRO_RS_INCLUDE_ONLY",
+        global = true
+    )]
+    #[serde(rename = "include-only")]
+    pub include_only: Option<Vec<String>>,
+
+    /// Only keep mirrors in these countries, by ISO code
+    #[arg(
+        long = "country-code",
+        value_name = "CODE",
+        value_delimiter = ',',
+        env = "MIRRO_RS_COUNTRY_CODES",
+        global = true
+    )]
+    #[serde(rename = "country-codes")]
+    pub country_codes: Option<Vec<String>>,
+
+    /// Drop mirrors in these countries, by ISO code
+    #[arg(
+        long = "exclude-country-code",
+        value_name = "CODE",
+        value_delimiter = ',',
+        env = "MIRRO_RS_EXCLUDE_COUNTRY_CODES",
+        global = true
+    )]
+    #[serde(rename = "exclude-country-codes")]
+    pub exclude_country_codes: Option<Vec<String>>,
 }
 
-#[derive(Default, Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, ValueEnum, Deserialize)]
-#[serde(rename_all = "lowercase")]
+/// A value the selected mirrors can be sorted on
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum SelectionSort {
     Percentage,
     Delay,
     Duration,
-    #[default]
     Score,
 }
 
-fn enable() -> bool {
-    true
+impl SelectionSort {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SelectionSort::Percentage => "completion",
+            SelectionSort::Delay => "delay",
+            SelectionSort::Duration => "duration",
+            SelectionSort::Score => "score",
+        }
+    }
+
+    /// Whether the best mirrors come first when sorting in descending order
+    pub fn higher_is_better(&self) -> bool {
+        *self == SelectionSort::Percentage
+    }
+}
+
+/// A single key of a [`SortSpec`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SortKey {
+    pub field: SelectionSort,
+    pub descending: bool,
+}
+
+impl SortKey {
+    /// Sorts on `field`, best first
+    pub fn best_first(field: SelectionSort) -> Self {
+        Self {
+            field,
+            descending: field.higher_is_better(),
+        }
+    }
+}
+
+/// Keys to sort the selected mirrors on, most significant first. Mirrors
+/// that tie on every key are sorted by URL
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SortSpec(pub Vec<SortKey>);
+
+impl Default for SortSpec {
+    fn default() -> Self {
+        Self(vec![SortKey::best_first(SelectionSort::Score)])
+    }
+}
+
+impl FromStr for SortSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keys: Vec<SortKey> = Vec::new();
+        for key in s.split(',').map(str::trim) {
+            let (name, descending) = match key.strip_prefix('-') {
+                Some(name) => (name, true),
+                None => (key.strip_prefix('+').unwrap_or(key), false),
+            };
+            let key = match name.to_ascii_lowercase().as_str() {
+                "completion" => SortKey {
+                    field: SelectionSort::Percentage,
+                    descending,
+                },
+                // the name used before several keys could be given, which
+                // always sorted the most complete mirrors first
+                "percentage" => SortKey::best_first(SelectionSort::Percentage),
+                "delay" => SortKey {
+                    field: SelectionSort::Delay,
+                    descending,
+                },
+                "duration" => SortKey {
+                    field: SelectionSort::Duration,
+                    descending,
+                },
+                "score" => SortKey {
+                    field: SelectionSort::Score,
+                    descending,
+                },
+                "" => return Err(String::from("sort keys must not be empty")),
+                _ => {
+                    return Err(format!(
+                        "unknown sort key `{name}`, expected completion, delay, duration or score"
+                    ))
+                }
+            };
+            if keys.iter().any(|f| f.field == key.field) {
+                return Err(format!("`{}` is given more than once", key.field.name()));
+            }
+            keys.push(key);
+        }
+        Ok(Self(keys))
+    }
+}
+
+impl TryFrom<String> for SortSpec {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<SortSpec> for String {
+    fn from(value: SortSpec) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for SortSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let keys = self
+            .0
+            .iter()
+            .map(|key| {
+                let sign = if key.descending { "-" } else { "" };
+                format!("{sign}{}", key.field.name())
+            })
+            .collect::<Vec<_>>();
+        write!(f, "{}", keys.join(","))
+    }
+}
+
+fn disable() -> Option<bool> {
+    Some(false)
+}
+
+fn required() -> Option<Requirement> {
+    Some(Requirement::Required)
 }
 
 fn completion() -> Option<u8> {
@@ -143,8 +736,8 @@ fn default_export() -> Option<u16> {
     Some(DEFAULT_MIRROR_COUNT)
 }
 
-fn sort() -> Option<SelectionSort> {
-    Some(SelectionSort::Score)
+fn sort() -> Option<SortSpec> {
+    Some(SortSpec::default())
 }
 
 fn view() -> Option<ViewSort> {
@@ -156,7 +749,7 @@ fn filters() -> Option<Vec<Protocol>> {
 }
 
 #[cfg_attr(test, derive(Default))]
-#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, ValueEnum, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, ValueEnum, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Protocol {
     #[cfg_attr(test, default)]
@@ -172,9 +765,90 @@ pub enum Protocol {
     Ipv6,
     #[value(skip)]
     Isos,
+    #[value(skip)]
+    ExcludeIpv4,
+    #[value(skip)]
+    ExcludeIpv6,
+    #[value(skip)]
+    ExcludeIsos,
+}
+
+/// Whether mirrors must have a property (`true`), must not have it
+/// (`exclude`) or may have it or not (`false`)
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(try_from = "RequirementValue", into = "RequirementValue")]
+pub enum Requirement {
+    #[default]
+    Any,
+    Required,
+    Excluded,
+}
+
+/// How a [`Requirement`] is written in configuration files, where a boolean
+/// keeps its former meaning
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum RequirementValue {
+    Bool(bool),
+    Word(String),
+}
+
+impl From<bool> for Requirement {
+    fn from(value: bool) -> Self {
+        if value {
+            Self::Required
+        } else {
+            Self::Any
+        }
+    }
+}
+
+impl FromStr for Requirement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "true" => Ok(Self::Required),
+            "false" => Ok(Self::Any),
+            "exclude" => Ok(Self::Excluded),
+            _ => Err(format!("`{s}` is not one of true, false or exclude")),
+        }
+    }
+}
+
+impl TryFrom<RequirementValue> for Requirement {
+    type Error = String;
+
+    fn try_from(value: RequirementValue) -> Result<Self, Self::Error> {
+        match value {
+            RequirementValue::Bool(value) => Ok(value.into()),
+            RequirementValue::Word(value) => value.parse(),
+        }
+    }
+}
+
+impl From<Requirement> for RequirementValue {
+    fn from(value: Requirement) -> Self {
+        match value {
+            Requirement::Any => Self::Bool(false),
+            Requirement::Required => Self::Bool(true),
+            Requirement::Excluded => Self::Word(String::from("exclude")),
+        }
+    }
+}
+
+/// Where the managed block goes relative to the other lines in the outfile
+#[derive(Debug, PartialEq, Eq, Clone, Copy, ValueEnum, Deserialize, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Placement {
+    Before,
+    #[default]
+    After,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, ValueEnum, Deserialize, Default)]
+#[derive(
+    Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, ValueEnum, Deserialize, Serialize, Default,
+)]
 #[serde(rename_all = "lowercase")]
 pub enum ViewSort {
     #[default]
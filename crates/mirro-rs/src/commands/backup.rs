@@ -0,0 +1,67 @@
+use anyhow::{Context, Result};
+
+use crate::{config::Configuration, mirrorlist};
+
+/// Lists the backups of the outfile, newest first
+pub async fn list(config: Configuration) -> Result<()> {
+    let backups = config.backups.list(&config.outfile).await?;
+    let current = tokio::fs::read_to_string(&config.outfile).await.ok();
+
+    match config.backups.dir {
+        Some(ref dir) => println!(
+            "backups of {} in {}",
+            config.outfile.display(),
+            dir.display()
+        ),
+        None => println!("no backup directory is available on this platform"),
+    }
+    if backups.is_empty() {
+        println!("  none");
+    }
+
+    for backup in backups {
+        let taken = backup
+            .taken()
+            .map(|f| f.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+            .unwrap_or_default();
+        print!("  {}  {taken}", backup.timestamp);
+
+        match tokio::fs::read_to_string(&backup.path).await {
+            Ok(contents) => {
+                let servers = mirrorlist::parse(&contents);
+                print!("  {} servers", servers.len());
+                if let Some(first) = servers.first() {
+                    print!(", first {first}");
+                }
+                if current.as_ref() == Some(&contents) {
+                    print!(" (current)");
+                }
+                println!();
+            }
+            Err(e) => println!("  unreadable: {e}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Restores the outfile from a backup, after backing up the outfile itself
+pub async fn rollback(config: Configuration, to: Option<String>) -> Result<()> {
+    let backup = config.backups.find(&config.outfile, to.as_deref()).await?;
+    let contents = tokio::fs::read(&backup.path)
+        .await
+        .with_context(|| format!("could not read {}", backup.path.display()))?;
+
+    config.backups.create(&config.outfile).await?;
+    mirrorlist::write::atomic(&config.outfile, &contents)
+        .await
+        .with_context(|| format!("could not write {}", config.outfile.display()))?;
+
+    println!(
+        "restored {} from the backup taken at {}",
+        config.outfile.display(),
+        backup.timestamp
+    );
+
+    Ok(())
+}
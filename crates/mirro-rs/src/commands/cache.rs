@@ -0,0 +1,65 @@
+use std::{path::Path, time::SystemTime};
+
+use anyhow::{Context, Result};
+
+use crate::{cli::CacheCommand, tui::io::handler::cache_file};
+
+pub async fn run(action: CacheCommand) -> Result<()> {
+    let cache = cache_file().context("no cache directory is available on this platform")?;
+    match action {
+        CacheCommand::Clear => match tokio::fs::remove_file(&cache).await {
+            Ok(()) => println!("removed {}", cache.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                println!("nothing cached at {}", cache.display())
+            }
+            Err(e) => return Err(e).context(format!("could not remove {}", cache.display())),
+        },
+        CacheCommand::Show => describe(&cache, None).await,
+    }
+    Ok(())
+}
+
+/// Prints the location, age and contents of the cached mirrorlist. If a `ttl`
+/// (in hours) is given, whether the cache is still fresh is printed as well
+pub async fn describe(cache: &Path, ttl: Option<u16>) {
+    println!("cache: {}", cache.display());
+
+    let metadata = match tokio::fs::metadata(cache).await {
+        Ok(metadata) => metadata,
+        Err(_) => {
+            println!("  not cached");
+            return;
+        }
+    };
+
+    println!("  size: {} bytes", metadata.len());
+    if let Ok(age) = metadata
+        .modified()
+        .map(|f| SystemTime::now().duration_since(f).unwrap_or_default())
+    {
+        let hours = age.as_secs() / 3600;
+        let minutes = (age.as_secs() / 60) % 60;
+        print!("  updated: {hours}h {minutes}m ago");
+        match ttl {
+            Some(ttl) if hours < ttl.into() => println!(" (fresh, ttl {ttl}h)"),
+            Some(ttl) => println!(" (stale, ttl {ttl}h)"),
+            None => println!(),
+        }
+    }
+
+    match tokio::fs::read_to_string(cache)
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|contents| archlinux::parse_local(&contents).map_err(anyhow::Error::from))
+    {
+        Ok(mirrors) => {
+            let count: usize = mirrors.countries.iter().map(|f| f.mirrors.len()).sum();
+            println!("  last check: {}", mirrors.last_check);
+            println!(
+                "  mirrors: {count} from {} countries",
+                mirrors.countries.len()
+            );
+        }
+        Err(e) => println!("  unreadable: {e}"),
+    }
+}
@@ -0,0 +1,264 @@
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::{
+    cli::ConfigCommand,
+    config::{
+        sources::{self, Layer},
+        validate::Issue,
+    },
+    errors,
+};
+
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+use crate::{cli::ConfigFormat, config::sources::Source};
+
+/// Runs a config subcommand, returning the exit code. `file` is the
+/// configuration file that was read and the profile in use, if any, `layers`
+/// the values set at each level, from the highest precedence down, and
+/// `force` whether an existing file may be replaced
+pub fn run(
+    action: ConfigCommand,
+    issues: &[Issue],
+    file: Option<(&Path, Option<&str>)>,
+    layers: &[Layer],
+    outfile: bool,
+    #[cfg_attr(
+        not(any(feature = "json", feature = "toml", feature = "yaml")),
+        allow(unused_variables)
+    )]
+    force: bool,
+) -> i32 {
+    let valid = match action {
+        ConfigCommand::Check => check(issues, file, outfile),
+        #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+        ConfigCommand::Show {
+            format: Some(format),
+        } => {
+            for issue in issues {
+                eprintln!("error: {issue}");
+            }
+            print!("{}", render(&sources::resolve(layers), format, false));
+            issues.is_empty()
+        }
+        ConfigCommand::Show { .. } => show(issues, layers),
+        #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+        ConfigCommand::Init { format } => return init(format.unwrap_or_default(), force),
+    };
+    if valid {
+        0
+    } else {
+        errors::CONFIG
+    }
+}
+
+fn check(issues: &[Issue], file: Option<(&Path, Option<&str>)>, outfile: bool) -> bool {
+    for issue in issues {
+        eprintln!("error: {issue}");
+    }
+    if issues.is_empty() {
+        if !outfile {
+            eprintln!("warning: no outfile is set, exporting needs one");
+        }
+        match file {
+            Some((file, None)) => println!("{}: ok", file.display()),
+            Some((file, Some(profile))) => {
+                println!("{}: ok, using profile {profile}", file.display())
+            }
+            None => println!("no configuration file, the command line is ok"),
+        }
+    }
+    issues.is_empty()
+}
+
+fn show(issues: &[Issue], layers: &[Layer]) -> bool {
+    for issue in issues {
+        eprintln!("error: {issue}");
+    }
+
+    let settings = sources::resolve(layers)
+        .into_iter()
+        .map(|(key, value, source)| {
+            let value = match value {
+                Value::Null => String::from("-"),
+                Value::String(value) => value,
+                value => value.to_string(),
+            };
+            (key, value, source.to_string())
+        })
+        .collect::<Vec<_>>();
+    let width = |column: fn(&(String, String, String)) -> &String| {
+        settings
+            .iter()
+            .map(|f| column(f).len())
+            .max()
+            .unwrap_or_default()
+    };
+    let (key_width, value_width) = (width(|f| &f.0), width(|f| &f.1));
+    for (key, value, source) in &settings {
+        println!("{key:key_width$}  {value:value_width$}  {source}");
+    }
+    issues.is_empty()
+}
+
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+fn init(format: ConfigFormat, force: bool) -> i32 {
+    let Some((path, read_instead)) = crate::config::location(extension(format)) else {
+        eprintln!("error: the configuration directory could not be found");
+        return errors::WRITE;
+    };
+    if path.exists() && !force {
+        eprintln!(
+            "error: {} already exists, pass --force to replace it",
+            path.display()
+        );
+        return errors::CONFIG;
+    }
+
+    let written = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&path, template(format)));
+    if let Err(e) = written {
+        eprintln!("error: {}: {e}", path.display());
+        return errors::WRITE;
+    }
+    println!("wrote {}", path.display());
+    if let Some(other) = read_instead {
+        eprintln!(
+            "warning: {} is read instead, remove it to use the new file",
+            other.display()
+        );
+    }
+    0
+}
+
+/// A configuration file with every setting at its default value, each
+/// described by its help text (except in json, which has no comments)
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+pub fn template(format: ConfigFormat) -> String {
+    let header = format!(
+        "# {} configuration, generated by `{0} config init`\n\
+         # Settings given on the command line or in MIRRO_RS_* environment\n\
+         # variables take precedence over the ones in this file\n\n",
+        env!("CARGO_PKG_NAME")
+    );
+    let settings = render(&sources::resolve(&[Layer::defaults()]), format, true);
+    match extension(format) {
+        "json" => settings,
+        _ => header + &settings,
+    }
+}
+
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+fn extension(format: ConfigFormat) -> &'static str {
+    match format {
+        #[cfg(feature = "toml")]
+        ConfigFormat::Toml => "toml",
+        #[cfg(feature = "json")]
+        ConfigFormat::Json => "json",
+        #[cfg(feature = "yaml")]
+        ConfigFormat::Yaml => "yaml",
+    }
+}
+
+/// The settings (as returned by [`sources::resolve`]) as a configuration
+/// file. With `comments`, each setting is preceded by its help text and the
+/// ones without a value are kept, commented out
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+fn render(settings: &[(String, Value, &Source)], format: ConfigFormat, comments: bool) -> String {
+    use clap::CommandFactory;
+
+    let sections = settings.iter().fold(
+        Vec::<(&str, Vec<(&str, &Value)>)>::new(),
+        |mut sections, (key, value, _)| {
+            let (section, key) = key.split_once('.').unwrap_or_default();
+            match sections.last_mut() {
+                Some((last, keys)) if *last == section => keys.push((key, value)),
+                _ => sections.push((section, vec![(key, value)])),
+            }
+            sections
+        },
+    );
+
+    if extension(format) == "json" {
+        let sections = sections
+            .into_iter()
+            .map(|(section, keys)| {
+                let keys = keys
+                    .into_iter()
+                    .filter(|(_, value)| !value.is_null())
+                    .map(|(key, value)| (key.to_string(), value.clone()))
+                    .collect();
+                (section.to_string(), Value::Object(keys))
+            })
+            .collect();
+        return serde_json::to_string_pretty(&Value::Object(sections)).unwrap_or_default() + "\n";
+    }
+
+    let command = crate::cli::ArgConfig::command();
+    let yaml = extension(format) == "yaml";
+    let (indent, assign) = if yaml { ("  ", ": ") } else { ("", " = ") };
+    let mut out = String::new();
+    for (idx, (section, keys)) in sections.into_iter().enumerate() {
+        if idx > 0 {
+            out.push('\n');
+        }
+        match yaml {
+            true => out.push_str(&format!("{section}:\n")),
+            false => out.push_str(&format!("[{section}]\n")),
+        }
+        for (key, value) in keys {
+            let arg = argument(&command, key);
+            if comments {
+                if let Some(help) = arg.and_then(|f| f.get_help()) {
+                    out.push_str(&format!("{indent}# {help}\n"));
+                }
+            }
+            let lines = match value {
+                Value::Null if !comments => continue,
+                Value::Null => {
+                    let name = arg
+                        .and_then(|f| f.get_value_names())
+                        .and_then(|f| f.first())
+                        .map(|f| f.to_lowercase())
+                        .unwrap_or_else(|| String::from("value"));
+                    match arg.map(|f| f.get_action()) {
+                        Some(clap::ArgAction::Append) => format!("#{key}{assign}[ <{name}> ]"),
+                        _ => format!("#{key}{assign}<{name}>"),
+                    }
+                }
+                value => entry(format, key, value),
+            };
+            for line in lines.lines() {
+                out.push_str(&format!("{indent}{line}\n"));
+            }
+        }
+    }
+    out
+}
+
+/// A single `key = value` (or `key: value`) line, or more for yaml lists
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+fn entry(format: ConfigFormat, key: &str, value: &Value) -> String {
+    let entry = serde_json::json!({ key: value });
+    match format {
+        #[cfg(feature = "toml")]
+        ConfigFormat::Toml => toml::to_string(&entry).unwrap_or_default(),
+        #[cfg(feature = "json")]
+        ConfigFormat::Json => entry.to_string(),
+        #[cfg(feature = "yaml")]
+        ConfigFormat::Yaml => serde_yaml::to_string(&entry).unwrap_or_default(),
+    }
+}
+
+/// The command line argument for a configuration file key, which is the one
+/// read from the environment variable named after it
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+fn argument<'a>(command: &'a clap::Command, key: &str) -> Option<&'a clap::Arg> {
+    let env = format!("MIRRO_RS_{}", key.to_uppercase().replace('-', "_"));
+    command
+        .get_arguments()
+        .find(|f| f.get_env().is_some_and(|f| f == env.as_str()))
+}
@@ -0,0 +1,189 @@
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use archlinux::{Country, Mirror};
+use itertools::Itertools;
+use serde_json::{Map, Value};
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    cli::{Column, ListArgs, ListFormat},
+    config::{self, Configuration},
+    direct::{load_mirrors, select_mirrors},
+};
+
+pub async fn run(configuration: Configuration, args: ListArgs) -> Result<()> {
+    let client = config::client(&configuration.client)?;
+    let config = Arc::new(Mutex::new(configuration));
+
+    let mirrorlist = load_mirrors(Arc::clone(&config), client).await?;
+    let mirrors = select_mirrors(&mirrorlist, config)?;
+
+    let output = render(args.format, &args.columns, &mirrors)?;
+    std::io::stdout().lock().write_all(output.as_bytes())?;
+
+    Ok(())
+}
+
+/// The given columns of `mirrors`, in `format`
+pub fn render(
+    format: ListFormat,
+    columns: &[Column],
+    mirrors: &[(&Country, &Mirror)],
+) -> Result<String> {
+    let rows = mirrors
+        .iter()
+        .map(|(country, mirror)| {
+            columns
+                .iter()
+                .map(|column| Field::new(*column, country, mirror))
+                .collect_vec()
+        })
+        .collect_vec();
+
+    Ok(match format {
+        ListFormat::Table => table(columns, &rows),
+        ListFormat::Csv => csv(columns, &rows),
+        ListFormat::Json => {
+            let rows = rows
+                .iter()
+                .map(|row| Value::Object(object(columns, row)))
+                .collect();
+            format!("{}\n", serde_json::to_string_pretty(&Value::Array(rows))?)
+        }
+        ListFormat::Ndjson => rows
+            .iter()
+            .map(|row| format!("{}\n", Value::Object(object(columns, row))))
+            .collect(),
+    })
+}
+
+/// A single value in a listing
+enum Field {
+    Text(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Missing,
+}
+
+impl Field {
+    fn new(column: Column, country: &Country, mirror: &Mirror) -> Self {
+        match column {
+            Column::Url => Self::Text(mirror.url.clone()),
+            Column::Host => mirror
+                .host()
+                .map_or(Self::Missing, |f| Self::Text(f.to_string())),
+            Column::Country => Self::Text(country.name.clone()),
+            Column::Code => Self::Text(country.code.clone()),
+            Column::Protocol => Self::Text(mirror.protocol.to_string()),
+            Column::Completion => Self::Float(f64::from(mirror.completion_pct) * 100.0),
+            Column::Delay => mirror.delay.map_or(Self::Missing, Self::Integer),
+            Column::Duration => mirror.duration_stddev.map_or(Self::Missing, Self::Float),
+            Column::Score => mirror.score.map_or(Self::Missing, Self::Float),
+            Column::LastSync => mirror
+                .last_sync
+                .map_or(Self::Missing, |f| Self::Text(f.to_rfc3339())),
+            Column::Ipv4 => Self::Bool(mirror.ipv4),
+            Column::Ipv6 => Self::Bool(mirror.ipv6),
+            Column::Isos => Self::Bool(mirror.isos),
+        }
+    }
+
+    fn text(&self, missing: &str) -> String {
+        match self {
+            Field::Text(text) => text.clone(),
+            Field::Integer(value) => value.to_string(),
+            Field::Float(value) => format!("{value:.2}"),
+            Field::Bool(value) => value.to_string(),
+            Field::Missing => missing.to_string(),
+        }
+    }
+
+    fn json(&self) -> Value {
+        match self {
+            Field::Text(text) => Value::from(text.as_str()),
+            Field::Integer(value) => Value::from(*value),
+            Field::Float(value) => Value::from(*value),
+            Field::Bool(value) => Value::from(*value),
+            Field::Missing => Value::Null,
+        }
+    }
+}
+
+fn header(column: Column) -> &'static str {
+    match column {
+        Column::Url => "url",
+        Column::Host => "host",
+        Column::Country => "country",
+        Column::Code => "code",
+        Column::Protocol => "protocol",
+        Column::Completion => "completion",
+        Column::Delay => "delay",
+        Column::Duration => "duration",
+        Column::Score => "score",
+        Column::LastSync => "last_sync",
+        Column::Ipv4 => "ipv4",
+        Column::Ipv6 => "ipv6",
+        Column::Isos => "isos",
+    }
+}
+
+fn object(columns: &[Column], row: &[Field]) -> Map<String, Value> {
+    columns
+        .iter()
+        .zip(row)
+        .map(|(column, field)| (header(*column).to_string(), field.json()))
+        .collect()
+}
+
+fn table(columns: &[Column], rows: &[Vec<Field>]) -> String {
+    let headers = columns.iter().map(|f| header(*f).to_string()).collect_vec();
+    let cells = rows
+        .iter()
+        .map(|row| row.iter().map(|f| f.text("-")).collect_vec())
+        .collect_vec();
+
+    let widths = (0..columns.len())
+        .map(|idx| {
+            std::iter::once(&headers)
+                .chain(cells.iter())
+                .map(|row| row[idx].width())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect_vec();
+
+    std::iter::once(&headers)
+        .chain(cells.iter())
+        .map(|row| {
+            let line = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell}{}", " ".repeat(width - cell.width())))
+                .join("  ");
+            format!("{}\n", line.trim_end())
+        })
+        .collect()
+}
+
+fn csv(columns: &[Column], rows: &[Vec<Field>]) -> String {
+    let escape = |value: String| {
+        if value.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value
+        }
+    };
+
+    std::iter::once(columns.iter().map(|f| header(*f).to_string()).collect_vec())
+        .chain(
+            rows.iter()
+                .map(|row| row.iter().map(|f| escape(f.text(""))).collect_vec()),
+        )
+        .map(|row| format!("{}\n", row.join(",")))
+        .collect()
+}
@@ -0,0 +1,7 @@
+pub mod backup;
+pub mod cache;
+pub mod config;
+pub mod list;
+pub mod rate;
+pub mod reflector;
+pub mod status;
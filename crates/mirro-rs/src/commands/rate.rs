@@ -0,0 +1,47 @@
+use anyhow::Result;
+use tracing::error;
+
+use crate::{
+    config::{self, Configuration},
+    errors::Error,
+};
+
+pub async fn run(configuration: Configuration, urls: Vec<String>) -> Result<()> {
+    let client = config::client(&configuration.client)?;
+
+    let mut set = tokio::task::JoinSet::new();
+    for mut url in urls {
+        if !url.ends_with('/') {
+            url.push('/');
+        }
+        set.spawn(archlinux::rate_mirror_with_timeout(
+            url,
+            client.clone(),
+            configuration.client.timeout,
+        ));
+    }
+
+    let total = set.len();
+    let mut rated = Vec::with_capacity(total);
+    while let Some(res) = set.join_next().await {
+        match res {
+            Ok(Ok(result)) => rated.push(result),
+            Ok(Err(e)) => error!("{e}"),
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    let failed = total - rated.len();
+    if total != 0 && failed == total {
+        return Err(Error::Rating { total }.into());
+    } else if failed != 0 && configuration.strict {
+        return Err(Error::PartialRating { failed, total }.into());
+    }
+
+    rated.sort_by_key(|(duration, _)| *duration);
+    for (duration, url) in rated {
+        println!("{:.3}s\t{url}", duration.as_secs_f64());
+    }
+
+    Ok(())
+}
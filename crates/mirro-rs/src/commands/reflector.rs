@@ -0,0 +1,223 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use archlinux::ClientConfig;
+use clap::{Parser, ValueEnum};
+use itertools::Itertools;
+
+use crate::{
+    cli::{self, Placement, Protocol, SortSpec, ViewSort},
+    config::{
+        self, default_preference, preference, rules::Rules, with_user_agent, Configuration,
+        Requirements, DEFAULT_BACKUPS,
+    },
+    direct::{
+        load_mirrors, select_mirrors,
+        stages::{self, Candidate, Stages},
+    },
+    mirrorlist::{backup, Backups, Entry, Output, Preview, Quota},
+    tui::io::handler::IoAsyncHandler,
+};
+
+/// The subset of reflector's command line that scripts commonly use
+#[derive(Parser, Debug)]
+#[command(
+    name = "reflector",
+    version,
+    about = "Retrieve and filter a list of the latest Arch Linux mirrors (mirro-rs' reflector compatible mode)"
+)]
+pub struct ReflectorArgs {
+    /// Only return the n most recently synchronized servers
+    #[arg(short, long, value_name = "n")]
+    latest: Option<u16>,
+
+    /// Return the n fastest mirrors that meet the other criteria
+    #[arg(short, long, value_name = "n")]
+    fastest: Option<u16>,
+
+    /// Return at most n mirrors
+    #[arg(short, long, value_name = "n")]
+    number: Option<usize>,
+
+    /// Sort the mirrorlist
+    #[arg(long, value_enum)]
+    sort: Option<ReflectorSort>,
+
+    /// Restrict mirrors to selected countries (comma-separated or repeated)
+    #[arg(short, long, value_delimiter = ',')]
+    country: Vec<String>,
+
+    /// Match one of the given protocols (comma-separated or repeated)
+    #[arg(short, long, value_enum, value_delimiter = ',')]
+    protocol: Vec<Protocol>,
+
+    /// Only return mirrors that have synchronized in the last n hours
+    #[arg(short, long, value_name = "n")]
+    age: Option<f64>,
+
+    /// Set the minimum completion percent for the returned mirrors
+    #[arg(long, value_name = "[0-100]", default_value_t = 100.0)]
+    completion_percent: f64,
+
+    /// Save the mirrorlist to the given path instead of printing it
+    #[arg(long, value_name = "filepath")]
+    save: Option<PathBuf>,
+
+    /// The URL from which to retrieve the mirror data
+    #[arg(long)]
+    url: Option<String>,
+
+    /// Only return mirrors that support IPv4
+    #[arg(long)]
+    ipv4: bool,
+
+    /// Only return mirrors that support IPv6
+    #[arg(long)]
+    ipv6: bool,
+
+    /// Only return mirrors that host ISOs
+    #[arg(long)]
+    isos: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ReflectorSort {
+    /// Last server synchronization
+    Age,
+    /// Download rate
+    Rate,
+    /// Country name
+    Country,
+    /// Mirror status score
+    Score,
+    /// Mirror status delay
+    Delay,
+}
+
+impl From<&ReflectorArgs> for Configuration {
+    fn from(args: &ReflectorArgs) -> Self {
+        let protocols = if args.protocol.is_empty() {
+            vec![
+                Protocol::Https,
+                Protocol::Http,
+                Protocol::Ftp,
+                Protocol::Rsync,
+            ]
+        } else {
+            args.protocol.clone()
+        };
+
+        let requirements = Requirements {
+            ipv4: args.ipv4.into(),
+            ipv6: args.ipv6.into(),
+            isos: args.isos.into(),
+        };
+
+        Self {
+            outfile: args.save.clone().unwrap_or_default(),
+            // the number of mirrors is capped after sorting instead
+            export: u16::MAX,
+            filters: requirements.filters(protocols),
+            view: ViewSort::default(),
+            sort: SortSpec::default(),
+            country: args.country.clone(),
+            ttl: cli::DEFAULT_CACHE_TTL,
+            url: args
+                .url
+                .clone()
+                .unwrap_or_else(|| cli::ARCH_URL.to_string()),
+            completion_percent: args.completion_percent.clamp(0.0, 100.0).ceil() as u8,
+            // like reflector, an age of 0 (or less) does not filter anything
+            age: args
+                .age
+                .map_or(0, |f| f.ceil().clamp(0.0, u16::MAX.into()) as u16),
+            // rating is a sort, done after the stages
+            rate: false,
+            client: with_user_agent(ClientConfig::default()),
+            include: None,
+            direct: true,
+            one_per_host: false,
+            prefer: preference(default_preference()),
+            // like reflector, print the mirrorlist unless asked to save it
+            preview: Preview {
+                dry_run: args.save.is_none(),
+                diff: false,
+            },
+            backups: Backups {
+                dir: backup::default_dir(),
+                keep: DEFAULT_BACKUPS,
+            },
+            force: false,
+            strict: false,
+            placement: Placement::default(),
+            header: true,
+            comments: true,
+            stages: Stages {
+                latest: args.latest,
+                score: None,
+                fastest: args.fastest,
+            },
+            rules: Rules::default(),
+            quota: Quota::default(),
+            profile: None,
+        }
+    }
+}
+
+/// Runs reflector's pipeline: filter, keep the latest, keep the fastest, sort
+/// and finally keep the requested number of mirrors. Sorting comes after the
+/// stages here, unlike in direct mode
+pub async fn run(args: ReflectorArgs) -> Result<()> {
+    let configuration = Configuration::from(&args);
+    let client = config::client(&configuration.client)?;
+    let timeout = configuration.client.timeout;
+    let mut output = Output::from(&configuration);
+
+    let config = Arc::new(Mutex::new(configuration));
+    let mirrorlist = load_mirrors(Arc::clone(&config), client.clone()).await?;
+
+    let stages = config.lock().unwrap().stages;
+    let mut candidates = select_mirrors(&mirrorlist, config)?
+        .into_iter()
+        .map(Candidate::new)
+        .collect_vec();
+    stages.run(&mut candidates, &client, timeout, false).await?;
+
+    match args.sort {
+        Some(ReflectorSort::Age) => stages::sort_by_age(&mut candidates),
+        Some(ReflectorSort::Rate) => {
+            stages::rate(&mut candidates, &client, timeout).await;
+            stages::sort_by_rate(&mut candidates);
+        }
+        Some(ReflectorSort::Country) => {
+            candidates.sort_by(|a, b| a.country.name.cmp(&b.country.name))
+        }
+        Some(ReflectorSort::Score) => stages::sort_by_score(&mut candidates),
+        Some(ReflectorSort::Delay) => {
+            candidates.sort_by_key(|f| f.mirror.delay.unwrap_or(i64::MAX))
+        }
+        None => {}
+    }
+
+    if let Some(number) = args.number {
+        candidates.truncate(number);
+    }
+
+    if let Some(ref mut header) = output.header {
+        header.last_check = Some(mirrorlist.last_check);
+        header.sort = args
+            .sort
+            .and_then(|f| f.to_possible_value())
+            .map_or_else(|| String::from("none"), |f| f.get_name().to_string());
+    }
+
+    let entries = candidates.into_iter().map(Entry::from).collect_vec();
+    output.count = entries.len();
+
+    IoAsyncHandler::write_to_file(output, &entries, None, None).await?;
+
+    Ok(())
+}
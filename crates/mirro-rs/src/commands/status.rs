@@ -0,0 +1,24 @@
+use anyhow::Result;
+
+use crate::{
+    commands::cache,
+    config::{self, Configuration},
+    tui::io::handler::cache_file,
+};
+
+pub async fn run(configuration: Configuration) -> Result<()> {
+    let client = config::client(&configuration.client)?;
+
+    print!("source: {}", configuration.url);
+    match client.head(&configuration.url).send().await {
+        Ok(response) => println!(" ({})", response.status()),
+        Err(e) => println!(" (unreachable: {e})"),
+    }
+
+    match cache_file() {
+        Some(cache) => cache::describe(&cache, Some(configuration.ttl)).await,
+        None => println!("cache: no cache directory is available on this platform"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use anyhow::Result;
+use archlinux::Country;
+use itertools::Itertools;
+
+use crate::errors::Error;
+
+/// Common names for countries that the upstream mirror status lists under a
+/// different name, mapped to their ISO code. Keys are normalised, see
+/// [`normalise`]
+const ALIASES: &[(&str, &str)] = &[
+    ("uk", "GB"),
+    ("britain", "GB"),
+    ("greatbritain", "GB"),
+    ("england", "GB"),
+    ("scotland", "GB"),
+    ("wales", "GB"),
+    ("usa", "US"),
+    ("america", "US"),
+    ("unitedstatesofamerica", "US"),
+    ("holland", "NL"),
+    ("thenetherlands", "NL"),
+    ("czechrepublic", "CZ"),
+    ("korea", "KR"),
+    ("republicofkorea", "KR"),
+    ("russianfederation", "RU"),
+    ("turkiye", "TR"),
+    ("vietnam", "VN"),
+    ("macedonia", "MK"),
+    ("republicofmoldova", "MD"),
+    ("deutschland", "DE"),
+    ("reunion", "RE"),
+];
+
+/// The least similarity for a country to be suggested in place of an unknown one
+const SUGGESTION_THRESHOLD: f64 = 0.8;
+
+/// How many countries are suggested for an unknown one
+const SUGGESTIONS: usize = 3;
+
+/// Lower case ASCII letters and digits only, so that `"north-macedonia"`
+/// matches `"North Macedonia"`
+fn normalise(value: &str) -> String {
+    value
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|f| f.to_ascii_lowercase())
+        .collect()
+}
+
+fn alias(value: &str) -> Option<&'static str> {
+    let value = normalise(value);
+    ALIASES
+        .iter()
+        .find_map(|(alias, code)| (*alias == value).then_some(*code))
+}
+
+/// Whether a country given on the command line or in the configuration
+/// file, as an ISO code, a name or an alias, refers to `country`
+pub fn matches(wanted: &str, country: &Country) -> bool {
+    let wanted = wanted.trim();
+    if wanted.is_empty() {
+        return false;
+    }
+
+    wanted.eq_ignore_ascii_case(&country.code)
+        || wanted.eq_ignore_ascii_case(&country.name)
+        || normalise(wanted) == normalise(&country.name)
+        || alias(wanted).is_some_and(|f| f.eq_ignore_ascii_case(&country.code))
+}
+
+/// Fails if any of the wanted countries refers to none of the countries in
+/// the mirrorlist, suggesting the closest ones instead
+pub fn check(countries: &[Country], wanted: &[String]) -> Result<()> {
+    let unknown = wanted
+        .iter()
+        .filter(|wanted| !countries.iter().any(|f| matches(wanted, f)))
+        .map(|wanted| {
+            let suggestions = suggest(countries, wanted);
+            if suggestions.is_empty() {
+                format!("unknown country \"{wanted}\"")
+            } else {
+                format!(
+                    "unknown country \"{wanted}\", did you mean {}?",
+                    suggestions.join(", ")
+                )
+            }
+        })
+        .collect_vec();
+
+    if !unknown.is_empty() {
+        return Err(Error::Config(format!(
+            "{}\nrun `mirro-rs list --columns country,code` to see the available countries",
+            unknown.join("\n")
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// The countries closest to `wanted` by name, code or alias, formatted as
+/// `Name (CODE)`
+fn suggest(countries: &[Country], wanted: &str) -> Vec<String> {
+    let wanted = normalise(wanted);
+    countries
+        .iter()
+        .filter(|f| !f.code.is_empty())
+        .map(|country| {
+            let aliases = ALIASES
+                .iter()
+                .filter(|(_, code)| code.eq_ignore_ascii_case(&country.code))
+                .map(|(alias, _)| alias.to_string());
+            let score = [normalise(&country.name), normalise(&country.code)]
+                .into_iter()
+                .chain(aliases)
+                .map(|f| strsim::jaro_winkler(&wanted, &f))
+                .fold(0.0, f64::max);
+            (score, country)
+        })
+        .filter(|(score, _)| *score >= SUGGESTION_THRESHOLD)
+        .sorted_by(|(a, _), (b, _)| b.total_cmp(a))
+        .take(SUGGESTIONS)
+        .map(|(_, country)| format!("{} ({})", country.name, country.code))
+        .collect()
+}
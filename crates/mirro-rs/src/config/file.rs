@@ -3,98 +3,262 @@ use std::{io::ErrorKind, path::PathBuf};
 use std::path::Path;
 
 use itertools::Itertools;
-use tracing::error;
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
 
 use crate::cli::ArgConfig;
 
-cfg_if::cfg_if! {
-    if #[cfg(feature = "toml")] {
-        fn default_args()->ArgConfig {
-            let config_str = include_str!("../../../../examples/mirro-rs.toml");
-            toml::from_str(config_str).unwrap()
-        }
-    } else if #[cfg(feature = "yaml")] {
-        fn default_args()->ArgConfig {
-            let config_str = include_str!("../../../../examples/mirro-rs.yaml");
-            serde_yaml::from_str(config_str).unwrap()
-        }
-    } else {
-        fn default_args()->ArgConfig {
-            let config_str = include_str!("../../../../examples/mirro-rs.json");
-            serde_json::from_str(config_str).unwrap()
-        }
-    }
+use super::{
+    profiles::{self, with_aliases, Layers},
+    rules::Pattern,
+    sources::{Layer, Source},
+    validate::{self, Issue, Origin, Problem},
+};
+
+/// A configuration file that was read, kept around to point at the lines
+/// problems were found on
+#[derive(Debug)]
+pub struct ConfigFile {
+    pub path: PathBuf,
+    /// The profile laid over the base options, if any
+    pub profile: Option<String>,
+    contents: String,
+    layers: Layers,
 }
 
-pub fn read_config_file(file: Option<impl AsRef<Path>>) -> (ArgConfig, Option<PathBuf>) {
-    let config_file = if let Some(ref file) = file {
-        let buf = file.as_ref().to_path_buf();
-        Some(check_file(&buf, None))
-    } else {
-        dirs::config_dir().map(|dir| get_config(dir, &extensions()))
-    };
-    match config_file {
-        Some(Some(opts)) => opts,
-        _ => (default_args(), None),
+impl ConfigFile {
+    /// Every problem with the values in this file, in every profile
+    pub fn issues(&self) -> Vec<Issue> {
+        let base = validate::problems(&self.layers.general, &self.layers.filters)
+            .into_iter()
+            .map(|problem| (None, problem));
+        let profiles = self.layers.profiles.iter().flat_map(|(name, profile)| {
+            let hostnames = profile
+                .hostnames
+                .iter()
+                .filter_map(|f| Pattern::new(f).err())
+                .map(|e| Problem {
+                    key: "hostnames",
+                    flag: "hostnames",
+                    message: format!("{e:#}"),
+                });
+            validate::problems(&profile.general, &profile.filters)
+                .into_iter()
+                .chain(hostnames)
+                .map(move |problem| (Some(name.as_str()), problem))
+        });
+
+        base.chain(profiles)
+            .map(|(profile, problem)| {
+                let position = self.locate(profile, problem.key);
+                let key = match profile {
+                    Some(name) => format!("{} (profile {name})", problem.key),
+                    None => problem.key.to_string(),
+                };
+                let issue = Issue {
+                    origin: Origin::File {
+                        path: self.path.clone(),
+                        position,
+                    },
+                    key: Some(key),
+                    message: problem.message,
+                };
+                (position, issue)
+            })
+            .sorted_by_key(|(position, _)| *position)
+            .map(|(_, issue)| issue)
+            .collect()
     }
-}
 
-fn check_file(file: &PathBuf, backup: Option<&PathBuf>) -> Option<(ArgConfig, Option<PathBuf>)> {
-    let err = |e| {
-        error!("{e}");
-    };
+    /// The values set in this file, those of the profile in use first
+    pub fn layers(&self) -> Vec<Layer> {
+        let Ok(mut values) = parse::<Value>(&self.path, &self.contents) else {
+            return vec![];
+        };
+        let mut profiles = values
+            .as_object_mut()
+            .and_then(|f| f.remove("profiles"))
+            .unwrap_or_default();
+        let profile = self.profile.as_ref().map(|name| {
+            let values = profiles.get_mut(name).map(Value::take).unwrap_or_default();
+            Layer::new(Source::Profile(name.clone()), with_aliases(values))
+        });
+        profile
+            .into_iter()
+            .chain([Layer::new(Source::File, with_aliases(values))])
+            .collect()
+    }
 
-    let call_backup = |backup: Option<&PathBuf>| {
-        if let Some(backup) = backup {
-            check_file(backup, None)
-        } else {
-            None
-        }
-    };
+    /// The line and column `key` is first set on, in any of the supported
+    /// formats. Keys of a profile are looked for after the line it starts on
+    fn locate(&self, profile: Option<&str>, key: &str) -> Option<(usize, usize)> {
+        let start = match profile {
+            Some(name) => {
+                let (table, quoted) = (format!("profiles.{name}"), format!("\"{name}\""));
+                self.contents.lines().position(|line| {
+                    let line = line.trim_start();
+                    line.contains(&table)
+                        || line.starts_with(&quoted)
+                        || line
+                            .strip_prefix(name)
+                            .is_some_and(|f| f.trim_start().starts_with(':'))
+                })?
+            }
+            None => 0,
+        };
 
-    let f = std::fs::read_to_string(file);
+        let quoted = format!("\"{key}\"");
+        self.contents
+            .lines()
+            .enumerate()
+            .skip(start)
+            .find_map(|(idx, line)| {
+                let trimmed = line.trim_start();
+                let rest = trimmed
+                    .strip_prefix(quoted.as_str())
+                    .or_else(|| trimmed.strip_prefix(key))?;
+                rest.trim_start()
+                    .starts_with(['=', ':'])
+                    .then_some((idx + 1, line.len() - trimmed.len() + 1))
+            })
+    }
+}
 
-    let err_type = || -> Result<ArgConfig, String> {
-        let ext = String::from_iter(extensions());
-        Err(format!("unsupported file extension: file must be: {ext}"))
+/// Reads the configuration file given on the command line or, failing that,
+/// the first one found in the configuration directory. The defaults are used
+/// if there is none. A file that cannot be read or parsed is never
+/// skipped in favour of another.
+///
+/// The profile asked for, or the one picked for this host, is laid over the
+/// base options
+pub fn read_config_file(
+    file: Option<impl AsRef<Path>>,
+    profile: Option<&str>,
+) -> Result<(ArgConfig, Option<ConfigFile>), Vec<Issue>> {
+    let candidates = match file {
+        Some(ref file) => vec![file.as_ref().to_path_buf()],
+        None => dirs::config_dir()
+            .map(|dir| candidates(&dir, &extensions()))
+            .unwrap_or_default(),
     };
+    let explicit = file.is_some();
 
-    match f {
-        #[allow(unused_variables)]
-        Ok(contents) => {
-            let result = if let Some(ext) = file.extension() {
-                match ext.to_string_lossy().to_string().as_str() {
-                    #[cfg(feature = "toml")]
-                    "toml" => toml::from_str::<ArgConfig>(&contents).map_err(|e| e.to_string()),
-                    #[cfg(feature = "json")]
-                    "json" => {
-                        serde_json::from_str::<ArgConfig>(&contents).map_err(|e| e.to_string())
-                    }
-                    #[cfg(feature = "yaml")]
-                    "yaml" | "yml" => {
-                        serde_yaml::from_str::<ArgConfig>(&contents).map_err(|e| e.to_string())
+    for path in candidates {
+        let issue = |message| Issue {
+            origin: Origin::File {
+                path: path.clone(),
+                position: None,
+            },
+            key: None,
+            message,
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                let layers = parse::<Layers>(&path, &contents).map_err(|e| vec![e])?;
+                let profile = layers.select(profile).map_err(|e| vec![issue(e)])?;
+                let args = match profile {
+                    Some(ref name) => {
+                        let value = parse(&path, &contents).map_err(|e| vec![e])?;
+                        let mut args = profiles::apply(value, name)
+                            .map_err(|e| vec![issue(format!("profile {name}: {e}"))])?;
+                        args.general.profile = Some(name.clone());
+                        args
                     }
-                    _ => err_type(),
-                }
-            } else {
-                err_type()
-            };
-
-            match result {
-                Ok(e) => Some((e, Some(file.to_owned()))),
-                Err(e) => {
-                    err(format!("config: {} -> {}", file.display(), e));
-                    call_backup(backup)
-                }
-            }
-        }
-        Err(e) => {
-            if e.kind() != ErrorKind::NotFound {
-                err(format!("config: {} -> {}", file.display(), e));
+                    None => layers.base(),
+                };
+                let file = ConfigFile {
+                    path,
+                    profile,
+                    contents,
+                    layers,
+                };
+                return Ok((args, Some(file)));
             }
-            call_backup(backup)
+            Err(e) if e.kind() == ErrorKind::NotFound && !explicit => {}
+            Err(e) => return Err(vec![issue(e.to_string())]),
         }
     }
+
+    match profile {
+        Some(_) => Err(vec![Issue {
+            origin: Origin::CommandLine,
+            key: Some(String::from("--profile")),
+            message: String::from("no configuration file was found to take it from"),
+        }]),
+        None => Ok((super::default_args(), None)),
+    }
+}
+
+fn parse<T: DeserializeOwned>(path: &Path, contents: &str) -> Result<T, Issue> {
+    let issue = |position, message: String| Issue {
+        origin: Origin::File {
+            path: path.to_path_buf(),
+            position,
+        },
+        key: None,
+        message,
+    };
+
+    let extension = path
+        .extension()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+    match extension.as_str() {
+        #[cfg(feature = "toml")]
+        "toml" => toml::from_str::<T>(contents).map_err(|e| {
+            let position = e.span().map(|f| position(contents, f.start));
+            issue(position, e.message().to_string())
+        }),
+        #[cfg(feature = "json")]
+        "json" => serde_json::from_str::<T>(contents).map_err(|e| {
+            let position = (e.line() != 0).then(|| (e.line(), e.column()));
+            issue(position, strip_position(e.to_string()))
+        }),
+        #[cfg(feature = "yaml")]
+        "yaml" | "yml" => serde_yaml::from_str::<T>(contents).map_err(|e| {
+            let position = e.location().map(|f| (f.line(), f.column()));
+            issue(position, strip_position(e.to_string()))
+        }),
+        _ => Err(issue(
+            None,
+            format!(
+                "unsupported file extension: file must be: {}",
+                extensions().join(", ")
+            ),
+        )),
+    }
+}
+
+/// Line and column (both starting at 1) of a byte offset
+#[cfg(feature = "toml")]
+fn position(contents: &str, offset: usize) -> (usize, usize) {
+    let before = &contents[..offset.min(contents.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |f| f + 1) + 1;
+    (line, column)
+}
+
+/// Leaves out the position that serde_json and serde_yaml append to their
+/// messages, as it is reported separately
+#[cfg(any(feature = "json", feature = "yaml"))]
+fn strip_position(message: String) -> String {
+    match message.rfind(" at line ") {
+        Some(idx) => message[..idx].to_string(),
+        None => message,
+    }
+}
+
+/// Where a new configuration file in the format with the given extension is
+/// written, and the existing file that would be read in its place, if any
+pub fn location(extension: &str) -> Option<(PathBuf, Option<PathBuf>)> {
+    let dir = dirs::config_dir()?;
+    let candidates = candidates(&dir, &extensions());
+    let target = candidates
+        .iter()
+        .position(|f| f.extension().is_some_and(|f| f == extension))?;
+    let shadowed_by = candidates[..target].iter().find(|f| f.exists()).cloned();
+    Some((candidates[target].clone(), shadowed_by))
 }
 
 fn extensions() -> Vec<String> {
@@ -112,24 +276,15 @@ fn extensions() -> Vec<String> {
     valid_extensions.into_iter().map(String::from).collect_vec()
 }
 
-fn get_config(mut dir: PathBuf, extension: &[String]) -> Option<(ArgConfig, Option<PathBuf>)> {
+/// Where configuration files are looked for, in order: `mirro-rs/mirro-rs.<ext>`
+/// then `mirro-rs.<ext>` in the configuration directory, for each extension
+fn candidates(dir: &Path, extensions: &[String]) -> Vec<PathBuf> {
     let crate_name = env!("CARGO_PKG_NAME");
-    let location = PathBuf::from(crate_name);
-    let mut file = PathBuf::from(crate_name);
-    let mut result: Option<(ArgConfig, Option<PathBuf>)> = None;
-    for i in extension.iter() {
-        let mut inner_location = location.clone();
-        file.set_extension(i);
-        inner_location.push(file.clone());
-
-        let mut alt = dir.clone();
-        dir.push(inner_location);
-        alt.push(file.clone());
-        let interim = check_file(&dir, Some(&alt));
-        if interim.is_some() {
-            result = interim;
-            break;
-        }
-    }
-    result
+    extensions
+        .iter()
+        .flat_map(|extension| {
+            let file = PathBuf::from(crate_name).with_extension(extension);
+            [dir.join(crate_name).join(&file), dir.join(file)]
+        })
+        .collect()
 }
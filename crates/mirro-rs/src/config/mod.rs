@@ -1,20 +1,33 @@
 #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
 mod file;
 
+pub mod countries;
 #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
-mod watch;
+mod profiles;
+pub mod rules;
+pub mod sources;
+pub mod validate;
+
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+pub mod watch;
 
 #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
 pub use watch::watch_config;
 
 #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
-pub use file::read_config_file;
+pub use file::{location, read_config_file};
 
 use std::path::PathBuf;
 
+use archlinux::{Client, ClientConfig};
+
+use self::rules::Rules;
+
 use crate::{
-    cli::{self, ArgConfig, Protocol, SelectionSort, ViewSort},
-    tui::view::sort::ExportSort,
+    cli::{self, ArgConfig, Placement, Protocol, Requirement, SortSpec, ViewSort},
+    direct::stages::Stages,
+    errors::Error,
+    mirrorlist::{backup, Backups, Preview, Quota},
 };
 
 #[cfg_attr(test, derive(Default))]
@@ -24,219 +37,449 @@ pub struct Configuration {
     pub export: u16,
     pub filters: Vec<Protocol>,
     pub view: ViewSort,
-    pub sort: ExportSort,
+    pub sort: SortSpec,
     pub country: Vec<String>,
     pub ttl: u16,
     pub url: String,
     pub completion_percent: u8,
     pub age: u16,
     pub rate: bool,
-    pub connection_timeout: Option<u64>,
+    pub client: ClientConfig,
     pub include: Option<Vec<String>>,
     pub direct: bool,
+    pub one_per_host: bool,
+    pub prefer: Vec<archlinux::Protocol>,
+    pub preview: Preview,
+    pub backups: Backups,
+    pub force: bool,
+    pub strict: bool,
+    pub placement: Placement,
+    pub header: bool,
+    pub comments: bool,
+    pub stages: Stages,
+    pub rules: Rules,
+    pub quota: Quota,
+    /// The profile read from the configuration file, if any
+    pub profile: Option<String>,
 }
 
-impl Configuration {
-    #[allow(clippy::too_many_arguments)]
-    pub fn new(
-        outfile: PathBuf,
-        export: u16,
-        mut filters: Vec<Protocol>,
-        view: ViewSort,
-        sort: SelectionSort,
-        country: Vec<String>,
-        ttl: u16,
-        url: String,
-        ipv4: bool,
-        isos: bool,
-        ipv6: bool,
-        completion_percent: u8,
-        age: u16,
-        rate: bool,
-        connection_timeout: Option<u64>,
-        include: Option<Vec<String>>,
-        direct: bool,
-    ) -> Self {
-        if ipv4 {
-            filters.push(Protocol::Ipv4)
-        }
-        if ipv6 {
-            filters.push(Protocol::Ipv6)
-        }
-        if isos {
-            filters.push(Protocol::Isos)
-        }
-        Self {
-            outfile,
-            export,
-            filters,
-            view,
-            sort: match sort {
-                SelectionSort::Percentage => ExportSort::Completion,
-                SelectionSort::Delay => ExportSort::MirroringDelay,
-                SelectionSort::Duration => ExportSort::Duration,
-                SelectionSort::Score => ExportSort::Score,
-            },
-            country,
-            ttl,
-            url,
-            completion_percent,
-            age,
-            rate,
-            connection_timeout,
-            include,
-            direct,
+/// Whether mirrors must support IPv4, IPv6 and ISOs, or must not
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Requirements {
+    pub ipv4: Requirement,
+    pub ipv6: Requirement,
+    pub isos: Requirement,
+}
+
+impl Requirements {
+    /// `protocols`, along with the markers that enforce these requirements
+    pub fn filters(self, mut protocols: Vec<Protocol>) -> Vec<Protocol> {
+        for (requirement, with, without) in [
+            (self.ipv4, Protocol::Ipv4, Protocol::ExcludeIpv4),
+            (self.ipv6, Protocol::Ipv6, Protocol::ExcludeIpv6),
+            (self.isos, Protocol::Isos, Protocol::ExcludeIsos),
+        ] {
+            match requirement {
+                Requirement::Any => {}
+                Requirement::Required => protocols.push(with),
+                Requirement::Excluded => protocols.push(without),
+            }
         }
+        protocols
+    }
+}
+
+/// `client`, identifying itself as mirro-rs unless it sets a user agent
+pub fn with_user_agent(mut client: ClientConfig) -> ClientConfig {
+    if client.user_agent.is_none() {
+        client.user_agent = Some(USER_AGENT.to_string());
     }
+    client
+}
+
+/// The protocols to prefer when keeping one URL per host, leaving out those
+/// no URL is served over
+pub fn preference(prefer: Vec<Protocol>) -> Vec<archlinux::Protocol> {
+    prefer
+        .into_iter()
+        .filter_map(|f| archlinux::Protocol::try_from(f).ok())
+        .collect()
+}
+
+/// The options of an empty configuration file, which are the defaults of the
+/// options that can be set in one
+pub fn default_args() -> ArgConfig {
+    serde_json::from_value(serde_json::json!({ "general": {}, "filters": {} })).unwrap_or_default()
+}
+
+pub fn default_protocols() -> Vec<Protocol> {
+    vec![Protocol::Http, Protocol::Https]
+}
+
+pub fn default_preference() -> Vec<Protocol> {
+    vec![
+        Protocol::Https,
+        Protocol::Http,
+        Protocol::Ftp,
+        Protocol::Rsync,
+    ]
+}
+
+pub const DEFAULT_BACKUPS: u16 = 5;
+
+const USER_AGENT: &str = concat!("mirro-rs/", env!("CARGO_PKG_VERSION"));
+
+/// Preview options only come from the command line
+pub fn preview(args: &cli::Args) -> Preview {
+    Preview {
+        dry_run: args.dry_run,
+        diff: args.diff,
+    }
+}
+
+pub fn client_config(args: &cli::Args) -> ClientConfig {
+    ClientConfig {
+        timeout: args.timeout,
+        proxy: args.proxy.clone(),
+        http_proxy: args.http_proxy.clone(),
+        https_proxy: args.https_proxy.clone(),
+        ignore_env_proxy: args.no_env_proxy.unwrap_or_default(),
+        root_certificates: args.ca_certs.clone().unwrap_or_default(),
+        user_agent: args.user_agent.clone(),
+        local_address: args.bind_address,
+        interface: args.interface.clone(),
+    }
+}
+
+/// The client for `config`, which cannot be built if a proxy, certificate
+/// bundle or interface is invalid
+pub fn client(config: &ClientConfig) -> Result<Client, Error> {
+    archlinux::get_client_with_config(config)
+        .map_err(|e| Error::Config(format!("{:#}", anyhow::Error::from(e))))
 }
 
 #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
-fn get_bools(args: &cli::Filters, config: &cli::Filters) -> (bool, bool, bool) {
-    let ipv4 = if !args.ipv4 && config.ipv4 {
-        true
-    } else {
-        args.ipv4
-    };
-
-    let ipv6 = if !args.ipv6 && config.ipv6 {
-        true
-    } else {
-        args.ipv6
-    };
-
-    let isos = if !args.isos && config.isos {
-        true
-    } else {
-        args.isos
-    };
-
-    (ipv4, ipv6, isos)
+fn merge_client(args: ClientConfig, config: ClientConfig) -> ClientConfig {
+    ClientConfig {
+        timeout: args.timeout.or(config.timeout),
+        proxy: args.proxy.or(config.proxy),
+        http_proxy: args.http_proxy.or(config.http_proxy),
+        https_proxy: args.https_proxy.or(config.https_proxy),
+        // merged by the caller, as an unset flag cannot be told from one that
+        // was turned off here
+        ignore_env_proxy: args.ignore_env_proxy,
+        root_certificates: if args.root_certificates.is_empty() {
+            config.root_certificates
+        } else {
+            args.root_certificates
+        },
+        user_agent: args.user_agent.or(config.user_agent),
+        local_address: args.local_address.or(config.local_address),
+        interface: args.interface.or(config.interface),
+    }
+}
+
+/// Folds the flags that undo an option (`--no-rate`, `--header` and so on)
+/// into it, so that only the option needs to be looked at. They come from the command line, so they win
+/// over the same options set through the environment
+pub fn fold_negations(args: &mut ArgConfig) {
+    let (general, filters) = (&mut args.general, &mut args.filters);
+    for (flag, off) in [
+        (&mut general.rate, general.no_rate),
+        (&mut general.direct, general.no_direct),
+        (&mut general.strict, general.no_strict),
+        (&mut general.one_per_host, general.no_one_per_host),
+        // these options turn something off, so their counterparts turn it on
+        (&mut general.no_header, general.header),
+        (&mut general.no_comments, general.comments),
+        (&mut general.no_env_proxy, general.env_proxy),
+    ] {
+        if off {
+            *flag = Some(false);
+        }
+    }
+    for (requirement, off) in [
+        (&mut filters.ipv4, filters.no_ipv4),
+        (&mut filters.ipv6, filters.no_ipv6),
+        (&mut filters.isos, filters.no_isos),
+    ] {
+        if off {
+            *requirement = Some(Requirement::Any);
+        }
+    }
 }
 
 #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
 impl From<(ArgConfig, ArgConfig)> for Configuration {
     fn from((mut args, mut config): (ArgConfig, ArgConfig)) -> Self {
-        let (ipv4, isos, ipv6) = get_bools(&args.filters, &config.filters);
+        let requirements = Requirements {
+            ipv4: args
+                .filters
+                .ipv4
+                .or(config.filters.ipv4)
+                .unwrap_or_default(),
+            ipv6: args
+                .filters
+                .ipv6
+                .or(config.filters.ipv6)
+                .unwrap_or_default(),
+            isos: args
+                .filters
+                .isos
+                .or(config.filters.isos)
+                .unwrap_or_default(),
+        };
+        let client = ClientConfig {
+            ignore_env_proxy: args
+                .general
+                .no_env_proxy
+                .or(config.general.no_env_proxy)
+                .unwrap_or_default(),
+            ..merge_client(client_config(&args.general), client_config(&config.general))
+        };
+        let preview = preview(&args.general);
+        // only required by the commands that export, see `main`
         let outfile = args
             .general
             .outfile
-            .unwrap_or_else(|| config.general.outfile.unwrap());
+            .or(config.general.outfile)
+            .unwrap_or_default();
         let export = args
             .general
             .export
-            .unwrap_or_else(|| config.general.export.unwrap());
-        let filters = args
-            .filters
-            .protocols
-            .unwrap_or_else(|| config.filters.protocols.unwrap());
+            .or(config.general.export)
+            .unwrap_or(cli::DEFAULT_MIRROR_COUNT);
+        let filters = requirements.filters(
+            args.filters
+                .protocols
+                .or(config.filters.protocols)
+                .unwrap_or_else(default_protocols),
+        );
         let view = args
             .general
             .view
-            .unwrap_or_else(|| config.general.view.unwrap());
+            .or(config.general.view)
+            .unwrap_or_default();
         let sort = args
             .general
             .sort
-            .unwrap_or_else(|| config.general.sort.unwrap());
-        let countries = args
+            .or(config.general.sort)
+            .unwrap_or_default();
+        let country = args
             .filters
             .country
-            .unwrap_or_else(|| config.filters.country.unwrap());
+            .or(config.filters.country)
+            .unwrap_or_default();
         let ttl = args
             .general
             .ttl
-            .unwrap_or_else(|| config.general.ttl.unwrap());
+            .or(config.general.ttl)
+            .unwrap_or(cli::DEFAULT_CACHE_TTL);
         let url = args
             .general
             .url
-            .unwrap_or_else(|| config.general.url.unwrap());
+            .or(config.general.url)
+            .unwrap_or_else(|| cli::ARCH_URL.to_string());
 
-        let completion = args
+        let completion_percent = args
             .filters
             .completion_percent
-            .unwrap_or_else(|| config.filters.completion_percent.unwrap());
+            .or(config.filters.completion_percent)
+            .unwrap_or(100);
 
-        let age = args
-            .filters
-            .age
-            .unwrap_or_else(|| config.filters.age.unwrap_or_default());
+        let age = args.filters.age.or(config.filters.age).unwrap_or_default();
 
-        let rate = if !args.general.rate && config.general.rate {
-            true
-        } else {
-            args.general.rate
-        };
-
-        let timoeut = if args.general.timeout.is_none() && config.general.timeout.is_some() {
-            config.general.timeout
-        } else {
-            args.general.timeout
-        };
+        let rate = args
+            .general
+            .rate
+            .or(config.general.rate)
+            .unwrap_or_default();
 
         let include = if args.general.include.is_none() && config.general.include.is_some() {
             std::mem::take(&mut config.general.include)
         } else {
             std::mem::take(&mut args.general.include)
         };
-        let direct = if !args.general.direct && config.general.direct {
-            true
-        } else {
-            args.general.direct
+        let direct = args
+            .general
+            .direct
+            .or(config.general.direct)
+            .unwrap_or_default();
+        let one_per_host = args
+            .general
+            .one_per_host
+            .or(config.general.one_per_host)
+            .unwrap_or_default();
+        let prefer = args
+            .general
+            .prefer
+            .or(config.general.prefer)
+            .unwrap_or_else(default_preference);
+        let backups = args
+            .general
+            .backups
+            .or(config.general.backups)
+            .unwrap_or(DEFAULT_BACKUPS);
+        let backup_dir = args.general.backup_dir.or(config.general.backup_dir);
+        let placement = args
+            .general
+            .placement
+            .or(config.general.placement)
+            .unwrap_or_default();
+        let header = !args
+            .general
+            .no_header
+            .or(config.general.no_header)
+            .unwrap_or_default();
+        let comments = !args
+            .general
+            .no_comments
+            .or(config.general.no_comments)
+            .unwrap_or_default();
+        let stages = Stages {
+            latest: args.general.latest.or(config.general.latest),
+            score: args.general.score.or(config.general.score),
+            fastest: args.general.fastest.or(config.general.fastest),
+        };
+        let rules = Rules::new(
+            args.filters
+                .exclude
+                .or(config.filters.exclude)
+                .unwrap_or_default(),
+            args.filters
+                .include_only
+                .or(config.filters.include_only)
+                .unwrap_or_default(),
+            args.filters
+                .country_codes
+                .or(config.filters.country_codes)
+                .unwrap_or_default(),
+            args.filters
+                .exclude_country_codes
+                .or(config.filters.exclude_country_codes)
+                .unwrap_or_default(),
+        );
+        let quota = Quota {
+            max_per_country: args
+                .general
+                .max_per_country
+                .or(config.general.max_per_country),
+            min_countries: args.general.min_countries.or(config.general.min_countries),
         };
 
-        Self::new(
-            outfile, export, filters, view, sort, countries, ttl, url, ipv4, isos, ipv6,
-            completion, age, rate, timoeut, include, direct,
-        )
+        Self {
+            outfile,
+            export,
+            filters,
+            view,
+            sort,
+            country,
+            ttl,
+            url,
+            completion_percent,
+            age,
+            rate,
+            client: with_user_agent(client),
+            include,
+            direct,
+            one_per_host,
+            prefer: preference(prefer),
+            preview,
+            backups: Backups {
+                dir: backup_dir.or_else(backup::default_dir),
+                keep: backups,
+            },
+            force: args.general.force,
+            strict: args
+                .general
+                .strict
+                .or(config.general.strict)
+                .unwrap_or_default(),
+            placement,
+            header,
+            comments,
+            stages,
+            rules,
+            quota,
+            profile: config.general.profile,
+        }
     }
 }
 
 #[cfg(any(test, not(any(feature = "json", feature = "toml", feature = "yaml"))))]
 impl From<ArgConfig> for Configuration {
     fn from(args: ArgConfig) -> Self {
-        let outfile = args
-            .general
-            .outfile
-            .or_else(|| crate::exit("outfile"))
-            .unwrap();
+        let client = client_config(&args.general);
+        let preview = preview(&args.general);
+        // only required by the commands that export, see `main`
+        let outfile = args.general.outfile.unwrap_or_default();
         let export = args.general.export.unwrap_or(cli::DEFAULT_MIRROR_COUNT);
-        let filters = args
-            .filters
-            .protocols
-            .unwrap_or_else(|| vec![Protocol::Http, Protocol::Https]);
+        // the same defaults as an empty configuration file
+        let requirements = Requirements {
+            ipv4: args.filters.ipv4.unwrap_or(Requirement::Required),
+            ipv6: args.filters.ipv6.unwrap_or(Requirement::Required),
+            isos: args.filters.isos.unwrap_or(Requirement::Required),
+        };
+        let filters =
+            requirements.filters(args.filters.protocols.unwrap_or_else(default_protocols));
         let view = args.general.view.unwrap_or_default();
         let sort = args.general.sort.unwrap_or_default();
-        let countries = args.filters.country.unwrap_or_default();
+        let country = args.filters.country.unwrap_or_default();
         let ttl = args.general.ttl.unwrap_or(cli::DEFAULT_CACHE_TTL);
         let url = args
             .general
             .url
             .unwrap_or_else(|| cli::ARCH_URL.to_string());
 
-        let completion = args.filters.completion_percent.unwrap_or(100);
+        let completion_percent = args.filters.completion_percent.unwrap_or(100);
 
         let age = args.filters.age.unwrap_or(0);
-        let rate = args.general.rate;
-        let timeout = args.general.timeout;
+        let rate = args.general.rate.unwrap_or_default();
         let include = args.general.include;
 
-        Self::new(
+        Self {
             outfile,
             export,
             filters,
             view,
             sort,
-            countries,
+            country,
             ttl,
             url,
-            args.filters.ipv4,
-            args.filters.isos,
-            args.filters.ipv6,
-            completion,
+            completion_percent,
             age,
             rate,
-            timeout,
+            client: with_user_agent(client),
             include,
-            args.general.direct,
-        )
+            direct: args.general.direct.unwrap_or_default(),
+            one_per_host: args.general.one_per_host.unwrap_or_default(),
+            prefer: preference(args.general.prefer.unwrap_or_else(default_preference)),
+            preview,
+            backups: Backups {
+                dir: args.general.backup_dir.or_else(backup::default_dir),
+                keep: args.general.backups.unwrap_or(DEFAULT_BACKUPS),
+            },
+            force: args.general.force,
+            strict: args.general.strict.unwrap_or_default(),
+            placement: args.general.placement.unwrap_or_default(),
+            header: !args.general.no_header.unwrap_or_default(),
+            comments: !args.general.no_comments.unwrap_or_default(),
+            stages: Stages {
+                latest: args.general.latest,
+                score: args.general.score,
+                fastest: args.general.fastest,
+            },
+            rules: Rules::new(
+                args.filters.exclude.unwrap_or_default(),
+                args.filters.include_only.unwrap_or_default(),
+                args.filters.country_codes.unwrap_or_default(),
+                args.filters.exclude_country_codes.unwrap_or_default(),
+            ),
+            quota: Quota {
+                max_per_country: args.general.max_per_country,
+                min_countries: args.general.min_countries,
+            },
+            profile: None,
+        }
     }
 }
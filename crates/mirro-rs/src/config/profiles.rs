@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::cli::{ArgConfig, Args, Filters};
+
+use super::rules::Pattern;
+
+/// The sections of a configuration file
+#[derive(Debug, Default, Deserialize)]
+pub struct Layers {
+    #[serde(default)]
+    pub general: Args,
+    #[serde(default)]
+    pub filters: Filters,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// Options layered on top of the `general` and `filters` sections when the
+/// profile is in use
+#[derive(Debug, Default, Deserialize)]
+pub struct Profile {
+    /// The profile is picked automatically on the hosts matching any of these
+    /// globs (or regular expressions, prefixed with `re:`)
+    #[serde(default)]
+    pub hostnames: Vec<String>,
+    #[serde(default)]
+    pub general: Args,
+    #[serde(default)]
+    pub filters: Filters,
+}
+
+impl Layers {
+    /// The profile that was asked for or, failing that, the first one (by
+    /// name) whose hostnames match this host
+    pub fn select(&self, wanted: Option<&str>) -> Result<Option<String>, String> {
+        match wanted {
+            Some(wanted) if self.profiles.contains_key(wanted) => Ok(Some(wanted.to_string())),
+            Some(wanted) if self.profiles.is_empty() => Err(format!(
+                "unknown profile `{wanted}`, the file does not define any"
+            )),
+            Some(wanted) => Err(format!(
+                "unknown profile `{wanted}`, expected one of: {}",
+                self.profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+            )),
+            None => Ok(hostname().and_then(|hostname| {
+                self.profiles
+                    .iter()
+                    .find(|(_, profile)| {
+                        profile
+                            .hostnames
+                            .iter()
+                            .filter_map(|f| Pattern::new(f).ok())
+                            .any(|f| f.matches(&hostname))
+                    })
+                    .map(|(name, _)| name.clone())
+            })),
+        }
+    }
+
+    /// The base options, without any profile
+    pub fn base(&self) -> ArgConfig {
+        ArgConfig {
+            command: None,
+            general: self.general.clone(),
+            filters: self.filters.clone(),
+        }
+    }
+}
+
+/// The options in `file` (the whole configuration file) with those of the
+/// given profile laid over them. Only the values the profile sets replace the
+/// base ones, lists included
+pub fn apply(mut file: Value, profile: &str) -> serde_json::Result<ArgConfig> {
+    let layer = file
+        .get_mut("profiles")
+        .and_then(|f| f.get_mut(profile))
+        .map(Value::take)
+        .map(with_aliases)
+        .unwrap_or_default();
+    if let Value::Object(ref mut file) = file {
+        file.remove("profiles");
+    }
+    // an alias in one layer must replace the key in the other
+    let mut file = with_aliases(file);
+    for section in ["general", "filters"] {
+        if let Some(values) = layer.get(section) {
+            merge(&mut file[section], values.clone());
+        }
+    }
+    serde_json::from_value(file)
+}
+
+/// Renames the keys that are also accepted under another name
+pub fn with_aliases(mut values: Value) -> Value {
+    if let Some(Value::Object(general)) = values.get_mut("general") {
+        if let Some(export) = general.remove("number") {
+            general.entry("export").or_insert(export);
+        }
+    }
+    values
+}
+
+fn merge(base: &mut Value, layer: Value) {
+    match (base, layer) {
+        (Value::Object(base), Value::Object(layer)) => {
+            for (key, value) in layer {
+                merge(base.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (base, layer) => *base = layer,
+    }
+}
+
+fn hostname() -> Option<String> {
+    ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .into_iter()
+        .find_map(|f| std::fs::read_to_string(f).ok())
+        .map(|f| f.trim().to_string())
+        .filter(|f| !f.is_empty())
+}
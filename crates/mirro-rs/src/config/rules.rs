@@ -0,0 +1,116 @@
+use anyhow::{anyhow, Result};
+use archlinux::{Country, Mirror};
+use itertools::Itertools;
+use regex::{Regex, RegexBuilder};
+use tracing::error;
+
+/// Prefix marking a pattern as a regular expression rather than a glob
+pub const REGEX_PREFIX: &str = "re:";
+
+/// A glob or, when prefixed with `re:`, a regular expression matched against
+/// the URL and the host of a mirror, or a hostname
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    source: String,
+    regex: Regex,
+}
+
+impl Pattern {
+    pub fn new(source: &str) -> Result<Self> {
+        let regex = match source.strip_prefix(REGEX_PREFIX) {
+            Some(regex) => RegexBuilder::new(regex),
+            None => RegexBuilder::new(&glob(source)),
+        }
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| {
+            // the syntax errors span several lines to point at the problem
+            let reason = e.to_string();
+            let reason = reason.lines().last().unwrap_or_default();
+            anyhow!(
+                "invalid pattern `{source}`: {}",
+                reason.trim_start_matches("error: ")
+            )
+        })?;
+
+        Ok(Self {
+            source: source.to_string(),
+            regex,
+        })
+    }
+
+    pub fn is_match(&self, mirror: &Mirror) -> bool {
+        self.matches(&mirror.url) || mirror.host().is_some_and(|f| self.matches(f))
+    }
+
+    pub fn matches(&self, value: &str) -> bool {
+        self.regex.is_match(value)
+    }
+}
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// An anchored regular expression for a glob where `*` matches any run of
+/// characters and `?` matches a single one
+fn glob(pattern: &str) -> String {
+    let body = pattern
+        .chars()
+        .map(|f| match f {
+            '*' => String::from(".*"),
+            '?' => String::from("."),
+            f => regex::escape(&f.to_string()),
+        })
+        .join("");
+    format!("^{body}$")
+}
+
+/// Allow and deny lists applied to every mirror on top of the other filters
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+    /// Mirrors matching any of these are dropped
+    pub exclude: Vec<Pattern>,
+    /// If not empty, only mirrors matching one of these are kept
+    pub include_only: Vec<Pattern>,
+    /// If not empty, only mirrors in these countries (ISO codes) are kept
+    pub country_codes: Vec<String>,
+    /// Mirrors in these countries (ISO codes) are dropped
+    pub exclude_country_codes: Vec<String>,
+}
+
+impl Rules {
+    /// Compiles the patterns, leaving out (and logging) the ones that are not
+    /// valid. These are rejected on startup, see `main`
+    pub fn new(
+        exclude: Vec<String>,
+        include_only: Vec<String>,
+        country_codes: Vec<String>,
+        exclude_country_codes: Vec<String>,
+    ) -> Self {
+        let compile = |patterns: Vec<String>| {
+            patterns
+                .iter()
+                .filter_map(|f| Pattern::new(f).map_err(|e| error!("{e}")).ok())
+                .collect_vec()
+        };
+        Self {
+            exclude: compile(exclude),
+            include_only: compile(include_only),
+            country_codes,
+            exclude_country_codes,
+        }
+    }
+
+    pub fn allows(&self, country: &Country, mirror: &Mirror) -> bool {
+        let code = |codes: &[String]| codes.iter().any(|f| f.eq_ignore_ascii_case(&country.code));
+
+        (self.country_codes.is_empty() || code(&self.country_codes))
+            && !code(&self.exclude_country_codes)
+            && (self.include_only.is_empty()
+                || self.include_only.iter().any(|f| f.is_match(mirror)))
+            && !self.exclude.iter().any(|f| f.is_match(mirror))
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt::Display;
+
+use clap::{CommandFactory, FromArgMatches};
+use serde_json::Value;
+
+use crate::cli::ArgConfig;
+
+/// The sections settings are grouped in, as in the configuration file
+const SECTIONS: [&str; 2] = ["general", "filters"];
+
+/// Where the value of a setting came from, in order of precedence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    CommandLine,
+    Environment,
+    #[cfg_attr(
+        not(any(feature = "json", feature = "toml", feature = "yaml")),
+        allow(dead_code)
+    )]
+    Profile(String),
+    #[cfg_attr(
+        not(any(feature = "json", feature = "toml", feature = "yaml")),
+        allow(dead_code)
+    )]
+    File,
+    Default,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::CommandLine => write!(f, "command line"),
+            Source::Environment => write!(f, "environment"),
+            Source::Profile(name) => write!(f, "profile {name}"),
+            Source::File => write!(f, "file"),
+            Source::Default => write!(f, "default"),
+        }
+    }
+}
+
+/// The settings given at one level, keyed like the configuration file
+#[derive(Debug)]
+pub struct Layer {
+    pub source: Source,
+    values: Value,
+}
+
+impl Layer {
+    pub fn new(source: Source, values: Value) -> Self {
+        Self { source, values }
+    }
+
+    /// The settings given on the command line, ignoring the environment
+    pub fn command_line() -> Self {
+        let command = ArgConfig::command().mut_args(|f| f.env(None));
+        let mut args = command
+            .try_get_matches()
+            .ok()
+            .and_then(|f| ArgConfig::from_arg_matches(&f).ok())
+            .unwrap_or_default();
+        super::fold_negations(&mut args);
+        Self::from_args(Source::CommandLine, &args)
+    }
+
+    /// The settings given on the command line or through `MIRRO_RS_*`
+    /// environment variables
+    pub fn environment(args: &ArgConfig) -> Self {
+        Self::from_args(Source::Environment, args)
+    }
+
+    /// The values used for the settings that are not set anywhere
+    pub fn defaults() -> Self {
+        Self::new(
+            Source::Default,
+            serde_json::to_value(super::default_args()).unwrap_or_default(),
+        )
+    }
+
+    /// Options that were not given, and flags that were not, are left out
+    fn from_args(source: Source, args: &ArgConfig) -> Self {
+        let mut values = serde_json::to_value(args).unwrap_or_default();
+        let unset = serde_json::to_value(ArgConfig::default()).unwrap_or_default();
+        for section in SECTIONS {
+            if let Some(Value::Object(values)) = values.get_mut(section) {
+                values.retain(|key, f| !f.is_null() && Some(&*f) != unset[section].get(key));
+            }
+        }
+        Self::new(source, values)
+    }
+
+    fn get(&self, section: &str, key: &str) -> Option<&Value> {
+        self.values
+            .get(section)
+            .and_then(|f| f.get(key))
+            .filter(|f| !f.is_null())
+    }
+}
+
+/// Every setting (as `section.key`), its value and the layer it was taken
+/// from, the first of `layers` to set it winning. The keys are those of the
+/// last layer, which should be [`Layer::defaults`]
+pub fn resolve(layers: &[Layer]) -> Vec<(String, Value, &Source)> {
+    let Some(defaults) = layers.last() else {
+        return vec![];
+    };
+    SECTIONS
+        .into_iter()
+        .flat_map(|section| {
+            let keys = defaults
+                .values
+                .get(section)
+                .and_then(Value::as_object)
+                .map(|f| f.keys().cloned().collect::<Vec<_>>())
+                .unwrap_or_default();
+            keys.into_iter().map(move |key| {
+                let (value, source) = layers
+                    .iter()
+                    .find_map(|layer| Some((layer.get(section, &key)?.clone(), &layer.source)))
+                    .unwrap_or((Value::Null, &defaults.source));
+                (format!("{section}.{key}"), value, source)
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,160 @@
+use std::{fmt::Display, path::PathBuf};
+
+use crate::cli::{Args, Filters};
+
+use super::rules::Pattern;
+
+/// A problem with a single option, regardless of where it was set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    /// The key in the configuration file
+    pub key: &'static str,
+    /// The command line flag, without the leading dashes
+    pub flag: &'static str,
+    pub message: String,
+}
+
+/// Where an [`Issue`] was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    CommandLine,
+    #[cfg_attr(
+        not(any(feature = "json", feature = "toml", feature = "yaml")),
+        allow(dead_code)
+    )]
+    File {
+        path: PathBuf,
+        /// Line and column, both starting at 1
+        position: Option<(usize, usize)>,
+    },
+}
+
+/// A problem found in the configuration, along with where it was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub origin: Origin,
+    /// The key (or flag) at fault, if the problem is with a single option
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl Issue {
+    pub fn command_line(problem: Problem) -> Self {
+        Self {
+            origin: Origin::CommandLine,
+            key: Some(format!("--{}", problem.flag)),
+            message: problem.message,
+        }
+    }
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.origin {
+            Origin::CommandLine => write!(f, "command line: ")?,
+            Origin::File {
+                path,
+                position: Some((line, column)),
+            } => write!(f, "{}:{line}:{column}: ", path.display())?,
+            Origin::File {
+                path,
+                position: None,
+            } => write!(f, "{}: ", path.display())?,
+        }
+        if let Some(ref key) = self.key {
+            write!(f, "{key}: ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Every problem with the given values. Values that cannot be parsed at all
+/// are rejected earlier, by clap or by the configuration file's parser
+pub fn problems(general: &Args, filters: &Filters) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut problem = |key, flag, message: String| problems.push(Problem { key, flag, message });
+
+    if let Some(ref outfile) = general.outfile {
+        let outfile = outfile.to_string_lossy();
+        if outfile.is_empty() {
+            problem("outfile", "outfile", String::from("must not be empty"));
+        } else if outfile.ends_with('/') {
+            problem(
+                "outfile",
+                "outfile",
+                format!("`{outfile}` must be a file, not a directory"),
+            );
+        }
+    }
+
+    for (key, value) in [
+        ("export", general.export),
+        ("latest", general.latest),
+        ("score", general.score),
+        ("fastest", general.fastest),
+        ("max-per-country", general.max_per_country),
+        ("min-countries", general.min_countries),
+    ] {
+        if value == Some(0) {
+            problem(key, key, String::from("must be at least 1"));
+        }
+    }
+
+    for protocol in general.prefer.iter().flatten() {
+        if archlinux::Protocol::try_from(*protocol).is_err() {
+            problem(
+                "prefer",
+                "prefer",
+                format!("`{protocol}` is not a protocol mirrors are served over, expected https, http, ftp or rsync"),
+            );
+        }
+    }
+
+    if filters.protocols.as_ref().is_some_and(Vec::is_empty) {
+        problem(
+            "protocols",
+            "protocols",
+            String::from("at least one protocol is needed"),
+        );
+    }
+
+    if let Some(completion) = filters.completion_percent.filter(|f| *f > 100) {
+        problem(
+            "completion-percent",
+            "completion-percent",
+            format!("{completion} is not a percentage between 0 and 100"),
+        );
+    }
+
+    for (key, patterns) in [
+        ("exclude", &filters.exclude),
+        ("include-only", &filters.include_only),
+    ] {
+        for pattern in patterns.iter().flatten() {
+            if let Err(e) = Pattern::new(pattern) {
+                problem(key, key, format!("{e:#}"));
+            }
+        }
+    }
+
+    for (key, flag, codes) in [
+        ("country-codes", "country-code", &filters.country_codes),
+        (
+            "exclude-country-codes",
+            "exclude-country-code",
+            &filters.exclude_country_codes,
+        ),
+    ] {
+        for code in codes.iter().flatten() {
+            if code.len() != 2 || !code.chars().all(|f| f.is_ascii_alphabetic()) {
+                problem(
+                    key,
+                    flag,
+                    format!("`{code}` is not a two letter ISO country code"),
+                );
+            }
+        }
+    }
+
+    problems
+}
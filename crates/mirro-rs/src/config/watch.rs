@@ -8,15 +8,21 @@ use std::{
 use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
 use tracing::error;
 
-use crate::config::read_config_file;
+use crate::{cli::ArgConfig, config::read_config_file};
 
 use super::Configuration;
 
-pub fn watch_config(path: Option<PathBuf>, configuration: Arc<Mutex<Configuration>>) {
+/// Reloads `configuration` whenever the file at `path` changes, with `args`
+/// (the command line and environment) still taking precedence over it
+pub fn watch_config(
+    path: Option<PathBuf>,
+    args: ArgConfig,
+    configuration: Arc<Mutex<Configuration>>,
+) {
     if let Some(dir) = dirs::config_dir() {
         if let Some(path) = path {
             tokio::task::spawn_blocking(move || {
-                if let Err(e) = async_watch(path, dir, configuration) {
+                if let Err(e) = async_watch(path, dir, &args, configuration) {
                     error!("error: {:?}", e)
                 }
             });
@@ -42,6 +48,7 @@ fn async_watcher() -> notify::Result<(RecommendedWatcher, mpsc::Receiver<notify:
 fn async_watch(
     path: impl AsRef<Path> + Debug,
     dir: impl AsRef<Path> + Debug,
+    args: &ArgConfig,
     config: Arc<Mutex<Configuration>>,
 ) -> notify::Result<()> {
     let (mut watcher, rx) = async_watcher()?;
@@ -59,29 +66,23 @@ fn async_watch(
                     .iter()
                     .any(|f| f.file_name() == path.as_ref().file_name())
                 {
-                    let (config_file, _) = read_config_file(Some(path.as_ref().to_path_buf()));
-                    let parsed_config = Configuration::new(
-                        config_file.general.outfile.unwrap(),
-                        config_file.general.export.unwrap(),
-                        config_file.filters.protocols.unwrap(),
-                        config_file.general.view.unwrap(),
-                        config_file.general.sort.unwrap(),
-                        config_file.filters.country.unwrap(),
-                        config_file.general.ttl.unwrap(),
-                        config_file.general.url.unwrap(),
-                        config_file.filters.ipv4,
-                        config_file.filters.isos,
-                        config_file.filters.ipv6,
-                        config_file.filters.completion_percent.unwrap(),
-                        config_file.filters.age.unwrap_or_default(),
-                        config_file.general.rate,
-                        config_file.general.timeout,
-                        config_file.general.include,
-                        config_file.general.direct,
-                    );
-
-                    let mut new_config = config.lock().unwrap();
-                    *new_config = parsed_config;
+                    // stay on the same profile, even if it would no longer
+                    // be picked for this host
+                    let profile = config.lock().unwrap().profile.clone();
+                    let issues = match read_config_file(Some(path.as_ref()), profile.as_deref()) {
+                        Ok((config_file, file)) => {
+                            let issues = file.map(|f| f.issues()).unwrap_or_default();
+                            if issues.is_empty() {
+                                reload(args, config_file, &config);
+                            }
+                            issues
+                        }
+                        Err(issues) => issues,
+                    };
+                    // keep the running configuration until the file is fixed
+                    for issue in issues {
+                        error!("{issue}");
+                    }
                 }
             }
             Err(e) => error!("watch error: {:?}", e),
@@ -89,3 +90,14 @@ fn async_watch(
     }
     Ok(())
 }
+
+/// Replaces the running configuration with `args` merged over the one read from
+/// the file, as at startup. The outfile is kept if neither sets one anymore
+pub fn reload(args: &ArgConfig, config_file: ArgConfig, config: &Mutex<Configuration>) {
+    let mut parsed_config = Configuration::from((args.clone(), config_file));
+    let mut new_config = config.lock().unwrap();
+    if parsed_config.outfile.as_os_str().is_empty() {
+        parsed_config.outfile = std::mem::take(&mut new_config.outfile);
+    }
+    *new_config = parsed_config;
+}
@@ -19,13 +19,15 @@ pub fn log(skip_tui: bool) {
         (Ok(layer), true) => {
             registry
                 .with(layer)
-                .with(tracing_subscriber::fmt::layer())
+                .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
                 .init();
         }
         // journald is typically available on Linux systems, but nowhere else. Portable software
         // should handle its absence gracefully.
         (Err(e), true) => {
-            registry.with(tracing_subscriber::fmt::layer()).init();
+            registry
+                .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+                .init();
             err_fn(e);
         }
         (Ok(layer), false) => {
@@ -42,7 +44,9 @@ pub fn log(skip_tui: bool) {
 
     #[cfg(not(unix))]
     if skip_tui {
-        registry.with(tracing_subscriber::fmt::layer()).init();
+        registry
+            .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+            .init();
     } else {
         registry.with(tui_logger::tracing_subscriber_layer()).init();
     }
@@ -1,27 +1,84 @@
+pub mod stages;
+
 use std::sync::{Arc, Mutex};
 
-use anyhow::{bail, Context, Result};
+use anyhow::Result;
 use archlinux::{
     chrono::{DateTime, Local},
-    get_client, ArchLinux, Mirror,
+    ArchLinux, Client, Country, Mirror,
 };
 use itertools::Itertools;
 use tracing::error;
 
 use crate::{
     cli::Protocol,
-    config::Configuration,
+    config::{self, countries, Configuration},
+    direct::stages::Candidate,
+    errors::Error,
+    mirrorlist::{Entry, Output},
     tui::io::{self, handler::IoAsyncHandler},
 };
 
 pub async fn begin(configuration: Configuration) -> Result<()> {
     let included = configuration.include.clone();
-    let connection_timeout = configuration.connection_timeout;
     let rate = configuration.rate;
-    let outfile = configuration.outfile.clone();
-    let export_count = configuration.export;
+    let stages = configuration.stages;
+    let sort = configuration.sort.clone();
+    let strict = configuration.strict;
+    let timeout = configuration.client.timeout;
+    let mut output = Output::from(&configuration);
+
+    let client = config::client(&configuration.client)?;
 
     let config = Arc::new(Mutex::new(configuration));
+    let mirrorlist = load_mirrors(Arc::clone(&config), client.clone()).await?;
+    if let Some(ref mut header) = output.header {
+        header.last_check = Some(mirrorlist.last_check);
+    }
+
+    let mirrors = select_mirrors(&mirrorlist, Arc::clone(&config))?;
+
+    let mirrors = {
+        let config = config.lock().unwrap();
+        if config.one_per_host {
+            archlinux::one_per_host(mirrors, &config.prefer, |(_, f)| {
+                (f.url.as_str(), f.protocol)
+            })
+        } else {
+            mirrors
+        }
+    };
+
+    let mut candidates = mirrors.into_iter().map(Candidate::new).collect_vec();
+    stages
+        .run(&mut candidates, &client, timeout, strict)
+        .await?;
+    // the fastest stage leaves the survivors sorted by download rate
+    if stages.fastest.is_none() {
+        sort.sort(&mut candidates);
+    }
+
+    let mut results = candidates.into_iter().map(Entry::from).collect_vec();
+
+    if let Some(included) = included {
+        results.extend(included.into_iter().map(Entry::from));
+    }
+
+    // the fastest stage has already rated and sorted the survivors
+    if rate && stages.fastest.is_none() {
+        IoAsyncHandler::rate_mirrors(results, None, None, output, None, client, timeout)
+            .await
+            .await??;
+    } else {
+        IoAsyncHandler::write_to_file(output, &results, None, None).await?;
+    }
+
+    Ok(())
+}
+
+/// Reads the cached mirrorlist if it is still fresh, otherwise fetches it from
+/// the configured source
+pub async fn load_mirrors(config: Arc<Mutex<Configuration>>, client: Client) -> Result<ArchLinux> {
     let (is_fresh, cache_file) = io::handler::is_fresh(Arc::clone(&config)).await;
     let mirrorlist = if is_fresh {
         match tokio::fs::read_to_string(cache_file.as_ref().unwrap()).await {
@@ -31,85 +88,56 @@ pub async fn begin(configuration: Configuration) -> Result<()> {
                     Ok(mirrors) => mirrors,
                     Err(e) => {
                         error!("{e}");
-                        get_new_mirrors(Arc::clone(&config), cache_file.as_ref()).await?
+                        get_new_mirrors(Arc::clone(&config), cache_file.as_ref(), client.clone())
+                            .await?
                     }
                 }
             }
             Err(e) => {
                 error!("{e}");
-                get_new_mirrors(Arc::clone(&config), cache_file.as_ref()).await?
+                get_new_mirrors(Arc::clone(&config), cache_file.as_ref(), client.clone()).await?
             }
         }
     } else {
-        get_new_mirrors(Arc::clone(&config), cache_file.as_ref()).await?
+        get_new_mirrors(Arc::clone(&config), cache_file.as_ref(), client.clone()).await?
     };
 
-    let mut results = mirrorlist
+    Ok(mirrorlist)
+}
+
+/// Mirrors from the configured countries that pass the configured filters,
+/// alongside the country they are in. Fails if a configured country is not in
+/// the mirrorlist
+pub fn select_mirrors(
+    mirrorlist: &ArchLinux,
+    config: Arc<Mutex<Configuration>>,
+) -> Result<Vec<(&Country, &Mirror)>> {
+    let wanted = config.lock().unwrap().country.clone();
+    countries::check(&mirrorlist.countries, &wanted)?;
+
+    let mirrors = mirrorlist
         .countries
         .iter()
-        .filter_map(|f| {
-            let results = f
+        .filter(|f| wanted.is_empty() || wanted.iter().any(|b| countries::matches(b, f)))
+        .flat_map(|country| {
+            country
                 .mirrors
                 .iter()
-                .filter(|f| filter_result(f, Arc::clone(&config)))
-                .filter(|_| {
-                    let conf = config.lock().unwrap();
-
-                    if conf.country.is_empty() {
-                        true
-                    } else {
-                        conf.country.iter().any(|b| b.eq_ignore_ascii_case(&f.name))
-                    }
-                })
-                .collect_vec();
-            if results.is_empty() {
-                None
-            } else {
-                Some(results)
-            }
+                .filter(|f| filter_result(country, f, Arc::clone(&config)))
+                .map(move |f| (country, f))
         })
-        .flatten()
-        .map(|f| f.url.clone())
         .collect_vec();
-
-    if let Some(mut included) = included {
-        results.append(&mut included);
-    }
-
-    let client = get_client(connection_timeout)?;
-
-    if rate {
-        if let Err(e) = IoAsyncHandler::rate_mirrors(
-            results,
-            None,
-            None,
-            outfile,
-            export_count.into(),
-            None,
-            client,
-        )
-        .await
-        .await
-        {
-            error!("{e}");
-        }
-    } else {
-        IoAsyncHandler::write_to_file(outfile, &results, export_count as usize, None, None).await;
-    }
-
-    Ok(())
+    Ok(mirrors)
 }
 
 async fn get_new_mirrors(
     config: Arc<Mutex<Configuration>>,
     cache_file: Option<&std::path::PathBuf>,
+    client: Client,
 ) -> Result<ArchLinux> {
-    let (url, timeout) = {
-        let config = config.lock().unwrap();
-        (config.url.clone(), config.connection_timeout)
-    };
+    let url = config.lock().unwrap().url.clone();
 
-    match archlinux::get_mirrors_with_raw(&url, timeout).await {
+    match archlinux::get_mirrors_with_client(&url, client).await {
         Ok((resp, str_value)) => {
             if let Some(cache) = cache_file {
                 if let Err(e) = tokio::fs::write(cache, str_value).await {
@@ -120,21 +148,33 @@ async fn get_new_mirrors(
         }
         Err(e) => {
             error!("{e}");
-            if let Some(f) = cache_file {
-                tokio::fs::read_to_string(f)
+            let cached = match cache_file {
+                Some(f) => tokio::fs::read_to_string(f)
                     .await
                     .ok()
-                    .and_then(|contents| archlinux::parse_local(&contents).ok())
-                    .context("could not read cache file")
-            } else {
-                bail!("No cache file was configured")
-            }
+                    .and_then(|contents| archlinux::parse_local(&contents).ok()),
+                None => None,
+            };
+            cached.ok_or_else(|| {
+                Error::Network {
+                    url,
+                    reason: e.to_string(),
+                }
+                .into()
+            })
         }
     }
 }
 
-pub fn filter_result(f: &Mirror, configuration: Arc<Mutex<Configuration>>) -> bool {
+pub fn filter_result(
+    country: &Country,
+    f: &Mirror,
+    configuration: Arc<Mutex<Configuration>>,
+) -> bool {
     let mut config = configuration.lock().unwrap();
+    if !config.rules.allows(country, f) {
+        return false;
+    }
 
     let res = |config: &Configuration, f: &Mirror| {
         let mut completion_ok = config.completion_percent as f32 <= f.completion_pct * 100.0;
@@ -152,6 +192,16 @@ pub fn filter_result(f: &Mirror, configuration: Arc<Mutex<Configuration>>) -> bo
         if v6_on {
             completion_ok = completion_ok && f.ipv6;
         }
+
+        for (excluded, has) in [
+            (Protocol::ExcludeIpv4, f.ipv4),
+            (Protocol::ExcludeIpv6, f.ipv6),
+            (Protocol::ExcludeIsos, f.isos),
+        ] {
+            if config.filters.contains(&excluded) {
+                completion_ok = completion_ok && !has;
+            }
+        }
         completion_ok
     };
 
@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+use archlinux::{Client, Country, Mirror};
+use tracing::error;
+
+use crate::{
+    cli::{SelectionSort, SortKey, SortSpec},
+    errors::Error,
+    mirrorlist::Entry,
+};
+
+/// How many mirrors each selection stage keeps, in the order the stages run.
+/// A stage that is not configured keeps every mirror
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Stages {
+    /// The most recently synced mirrors
+    pub latest: Option<u16>,
+    /// The mirrors with the best (lowest) upstream score
+    pub score: Option<u16>,
+    /// The mirrors that download the fastest, only rating the mirrors that
+    /// made it through the earlier stages
+    pub fastest: Option<u16>,
+}
+
+/// A mirror that passed the filters, along with its download time once rated
+pub struct Candidate<'a> {
+    pub country: &'a Country,
+    pub mirror: &'a Mirror,
+    pub rate: Option<Duration>,
+}
+
+impl<'a> Candidate<'a> {
+    pub fn new((country, mirror): (&'a Country, &'a Mirror)) -> Self {
+        Self {
+            country,
+            mirror,
+            rate: None,
+        }
+    }
+}
+
+impl From<Candidate<'_>> for Entry {
+    fn from(candidate: Candidate<'_>) -> Self {
+        Self {
+            rate: candidate.rate,
+            ..Entry::new(candidate.country, candidate.mirror)
+        }
+    }
+}
+
+impl Stages {
+    /// Fails if none of the survivors could be rated by the fastest stage or,
+    /// when `strict`, if any of them could not. `timeout` is the one `client`
+    /// was built with, see [archlinux::rate_mirror_with_timeout]
+    pub async fn run(
+        &self,
+        candidates: &mut Vec<Candidate<'_>>,
+        client: &Client,
+        timeout: Option<u64>,
+        strict: bool,
+    ) -> Result<(), Error> {
+        if let Some(latest) = self.latest {
+            sort_by_age(candidates);
+            candidates.truncate(latest.into());
+        }
+
+        if let Some(score) = self.score {
+            sort_by_score(candidates);
+            candidates.truncate(score.into());
+        }
+
+        if let Some(fastest) = self.fastest {
+            let total = candidates.len();
+            let failed = rate(candidates, client, timeout).await;
+            if total != 0 && failed == total {
+                return Err(Error::Rating { total });
+            } else if failed != 0 && strict {
+                return Err(Error::PartialRating { failed, total });
+            }
+            candidates.retain(|f| f.rate.is_some());
+            sort_by_rate(candidates);
+            candidates.truncate(fastest.into());
+        }
+        Ok(())
+    }
+}
+
+/// Most recently synced first
+pub fn sort_by_age(candidates: &mut [Candidate<'_>]) {
+    candidates.sort_by_key(|f| std::cmp::Reverse(f.mirror.last_sync));
+}
+
+/// Best score first, unscored mirrors last
+pub fn sort_by_score(candidates: &mut [Candidate<'_>]) {
+    SortSpec(vec![SortKey::best_first(SelectionSort::Score)]).sort(candidates);
+}
+
+/// Fastest first, unrated mirrors last
+pub fn sort_by_rate(candidates: &mut [Candidate<'_>]) {
+    candidates.sort_by_key(|f| f.rate.unwrap_or(Duration::MAX));
+}
+
+/// Rates the candidates that have not been rated yet, returning how many
+/// could not be
+pub async fn rate(
+    candidates: &mut [Candidate<'_>],
+    client: &Client,
+    timeout: Option<u64>,
+) -> usize {
+    let mut set = tokio::task::JoinSet::new();
+    for (idx, candidate) in candidates.iter().enumerate() {
+        if candidate.rate.is_none() {
+            let (url, client) = (candidate.mirror.url.clone(), client.clone());
+            set.spawn(async move {
+                (
+                    idx,
+                    archlinux::rate_mirror_with_timeout(url, client, timeout).await,
+                )
+            });
+        }
+    }
+
+    let mut failed = 0;
+    while let Some(res) = set.join_next().await {
+        match res {
+            Ok((idx, Ok((duration, _)))) => candidates[idx].rate = Some(duration),
+            Ok((_, Err(e))) => {
+                failed += 1;
+                error!("{e}")
+            }
+            Err(e) => {
+                failed += 1;
+                error!("{e}")
+            }
+        }
+    }
+    failed
+}
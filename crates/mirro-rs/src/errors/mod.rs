@@ -0,0 +1,85 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+/// Exit code for invalid options, configuration files or countries
+pub const CONFIG: i32 = 2;
+/// Exit code for when the mirrorlist could not be fetched and no cache was available
+pub const NETWORK: i32 = 3;
+/// Exit code for when no mirrors passed the filters
+pub const NO_MIRRORS: i32 = 4;
+/// Exit code for when mirrors could not be rated
+pub const RATING: i32 = 5;
+/// Exit code for when the outfile (or its backup) could not be written
+pub const WRITE: i32 = 6;
+
+#[derive(Error, Debug)]
+/// Failures that scripts running mirro-rs can tell apart through the exit code.
+/// Any other error exits with 1
+pub enum Error {
+    /// An option was not valid
+    #[error("{0}")]
+    Config(String),
+    /// The mirrorlist could not be fetched and there was no cache to fall back on
+    #[error("could not get the mirrorlist from {url} and no cache is available: {reason}")]
+    Network {
+        /// The configured source
+        url: String,
+        /// Why the request failed
+        reason: String,
+    },
+    /// No mirror passed the filters, so there was nothing to write
+    #[error("no mirrors matched, refusing to write an empty mirrorlist to {path:?} (use --force to write it anyway)")]
+    NoMirrors {
+        /// The outfile
+        path: PathBuf,
+    },
+    /// None of the mirrors could be rated
+    #[error("none of the {total} mirrors could be rated")]
+    Rating {
+        /// How many mirrors were rated
+        total: usize,
+    },
+    /// Some of the mirrors could not be rated, only fatal with `--strict`
+    #[error("{failed} of {total} mirrors could not be rated")]
+    PartialRating {
+        /// How many mirrors could not be rated
+        failed: usize,
+        /// How many mirrors were rated
+        total: usize,
+    },
+    /// The outfile could not be written
+    #[error("could not write {path:?}: {reason}")]
+    Write {
+        /// The file (or directory) that could not be written
+        path: PathBuf,
+        /// Why writing failed
+        reason: String,
+    },
+    /// The outfile could not be backed up, only fatal with `--strict`
+    #[error("could not back up {path:?}: {reason}")]
+    Backup {
+        /// The outfile
+        path: PathBuf,
+        /// Why the backup failed
+        reason: String,
+    },
+}
+
+impl Error {
+    /// The process exit code for this error
+    pub fn code(&self) -> i32 {
+        match self {
+            Error::Config(_) => CONFIG,
+            Error::Network { .. } => NETWORK,
+            Error::NoMirrors { .. } => NO_MIRRORS,
+            Error::Rating { .. } | Error::PartialRating { .. } => RATING,
+            Error::Write { .. } | Error::Backup { .. } => WRITE,
+        }
+    }
+}
+
+/// The process exit code for an error returned by a command
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    error.downcast_ref::<Error>().map_or(1, Error::code)
+}
@@ -1,58 +1,176 @@
 mod cli;
+mod commands;
 mod config;
 mod dbg;
 mod direct;
+mod errors;
+mod mirrorlist;
 #[cfg(test)]
 mod test;
 mod tui;
 
 use std::sync::{Arc, Mutex};
 
-use tracing::error;
+use cli::Command;
+use config::validate::Issue;
+use tracing::{error, info};
 
 #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
 use self::config::watch_config;
 
 #[tokio::main]
 async fn main() {
-    let args = <cli::ArgConfig as clap::Parser>::parse();
+    if let Some(args) = reflector_args() {
+        dbg::log(true);
+        if let Err(e) = commands::reflector::run(args).await {
+            error!("{e}");
+            std::process::exit(errors::exit_code(&e));
+        }
+        std::process::exit(0);
+    }
+
+    let mut args = <cli::ArgConfig as clap::Parser>::parse();
+    config::fold_negations(&mut args);
+    let command = args.command.take();
+    // a dry run without a diff never reads or writes the outfile
+    let exports = matches!(command, None | Some(Command::Tui) | Some(Command::Export))
+        && (args.general.diff || !args.general.dry_run)
+        || matches!(
+            command,
+            Some(Command::Backups) | Some(Command::Rollback { .. })
+        );
 
     #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
-    let (config, file) = config::read_config_file(args.general.config.as_ref());
+    let (config, file, file_issues) = match config::read_config_file(
+        args.general.config.as_ref(),
+        args.general.profile.as_deref(),
+    ) {
+        Ok((config, file)) => {
+            let issues = file.as_ref().map(|f| f.issues());
+            (config, file, issues.unwrap_or_default())
+        }
+        Err(issues) => (cli::ArgConfig::default(), None, issues),
+    };
+
+    #[cfg(not(any(feature = "json", feature = "toml", feature = "yaml")))]
+    let file_issues = Vec::new();
+
+    let issues = config::validate::problems(&args.general, &args.filters)
+        .into_iter()
+        .map(Issue::command_line)
+        .chain(file_issues)
+        .collect::<Vec<_>>();
 
     #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
-    if !check_outfile(&args.general) && !check_outfile(&config.general) {
-        exit("outfile");
-    }
+    let outfile = args.general.outfile.is_some() || config.general.outfile.is_some();
 
     #[cfg(not(any(feature = "json", feature = "toml", feature = "yaml")))]
-    if !check_outfile(&args.general) {
+    let outfile = args.general.outfile.is_some();
+
+    if let Some(Command::Config { action }) = command {
+        // from the highest precedence down
+        let mut layers = vec![
+            config::sources::Layer::command_line(),
+            config::sources::Layer::environment(&args),
+        ];
+        #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+        layers.extend(file.iter().flat_map(|f| f.layers()));
+        layers.push(config::sources::Layer::defaults());
+
+        #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+        let file = file
+            .as_ref()
+            .map(|f| (f.path.as_path(), f.profile.as_deref()));
+
+        #[cfg(not(any(feature = "json", feature = "toml", feature = "yaml")))]
+        let file = None;
+
+        let force = args.general.force;
+        std::process::exit(commands::config::run(
+            action, &issues, file, &layers, outfile, force,
+        ));
+    }
+
+    if !issues.is_empty() {
+        for issue in issues {
+            eprintln!("error: {issue}");
+        }
+        std::process::exit(errors::CONFIG);
+    }
+
+    if exports && !outfile {
         exit("outfile");
     }
 
+    // a changed configuration file is merged beneath these again
+    #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+    let watched_args = args.clone();
+
     #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
     let config = config::Configuration::from((args, config));
 
     #[cfg(not(any(feature = "json", feature = "toml", feature = "yaml")))]
     let config = config::Configuration::from(args);
 
-    dbg::log(config.direct);
-
-    if config.direct {
-        if let Err(e) = direct::begin(config).await {
-            error!("{e}")
-        }
+    // -d/--direct (on the command line or in the config file) is an alias
+    // for the export subcommand
+    let command = command.unwrap_or(if config.direct {
+        Command::Export
     } else {
-        let config = Arc::new(Mutex::new(config));
+        Command::Tui
+    });
 
-        #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
-        watch_config(file, Arc::clone(&config));
+    dbg::log(command != Command::Tui);
+    if let Some(ref profile) = config.profile {
+        info!("using profile {profile}");
+    }
+
+    let result = match command {
+        Command::Tui => {
+            let config = Arc::new(Mutex::new(config));
 
-        let _ = tui::start(config).await;
+            #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+            watch_config(file.map(|f| f.path), watched_args, Arc::clone(&config));
+
+            let _ = tui::start(config).await;
+            Ok(())
+        }
+        Command::Export => direct::begin(config).await,
+        Command::List(args) => commands::list::run(config, args).await,
+        Command::Rate { urls } => commands::rate::run(config, urls).await,
+        Command::Status => commands::status::run(config).await,
+        Command::Cache { action } => commands::cache::run(action).await,
+        Command::Backups => commands::backup::list(config).await,
+        Command::Rollback { to } => commands::backup::rollback(config, to).await,
+        Command::Config { .. } => unreachable!("the configuration is checked before it is used"),
+        Command::Reflector { .. } => unreachable!("reflector options are parsed before ours"),
+    };
+    if let Err(e) = result {
+        error!("{e}");
+        std::process::exit(errors::exit_code(&e));
     }
     std::process::exit(0);
 }
 
+/// reflector's options, if mirro-rs was invoked as `reflector` or with the
+/// `reflector` subcommand. These are parsed separately as they clash with
+/// mirro-rs' own global options
+fn reflector_args() -> Option<commands::reflector::ReflectorArgs> {
+    use clap::Parser;
+
+    let mut args = std::env::args_os();
+    let program = args.next()?;
+    if std::path::Path::new(&program).file_stem() == Some("reflector".as_ref()) {
+        Some(commands::reflector::ReflectorArgs::parse())
+    } else if args.next().is_some_and(|f| f == "reflector") {
+        Some(commands::reflector::ReflectorArgs::parse_from(
+            std::env::args_os().skip(1),
+        ))
+    } else {
+        None
+    }
+}
+
 pub fn exit(value: &str) -> ! {
     let cmd = clap::Command::new("mirro-rs");
     let mut err = clap::Error::new(clap::error::ErrorKind::ValueValidation).with_cmd(&cmd);
@@ -67,14 +185,3 @@ pub fn exit(value: &str) -> ! {
     );
     err.exit();
 }
-
-fn check_outfile(config: &cli::Args) -> bool {
-    if let Some(ref outfile) = config.outfile {
-        if outfile.to_string_lossy().ends_with('/') || outfile.to_string_lossy().is_empty() {
-            exit("outfile");
-        }
-        true
-    } else {
-        false
-    }
-}
@@ -0,0 +1,130 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use archlinux::chrono::{NaiveDateTime, Utc};
+use itertools::Itertools;
+
+/// Backups are named `<outfile name>-<timestamp>`, so that sorting them by
+/// name sorts them by age
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%3fZ";
+
+/// Where previous versions of the outfile are kept, and how many of them
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Backups {
+    /// `None` if the platform has no data directory and none was configured
+    pub dir: Option<PathBuf>,
+    /// How many backups to keep, `0` disables them
+    pub keep: u16,
+}
+
+/// A previous version of an outfile
+#[derive(Debug)]
+pub struct Backup {
+    pub path: PathBuf,
+    /// When the backup was taken, as it appears in the file name
+    pub timestamp: String,
+}
+
+impl Backup {
+    pub fn taken(&self) -> Option<NaiveDateTime> {
+        NaiveDateTime::parse_from_str(&self.timestamp, TIMESTAMP_FORMAT).ok()
+    }
+}
+
+/// Where backups are kept unless configured otherwise
+pub fn default_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|mut dir| {
+        dir.push(env!("CARGO_PKG_NAME"));
+        dir.push("backups");
+        dir
+    })
+}
+
+impl Backups {
+    /// Copies `outfile`, if there is one, to the backup directory and removes
+    /// the oldest backups beyond the ones to keep
+    pub async fn create(&self, outfile: &Path) -> Result<Option<PathBuf>> {
+        let dir = match self.dir {
+            Some(ref dir) if self.keep > 0 => dir,
+            _ => return Ok(None),
+        };
+        if !tokio::fs::try_exists(outfile).await? {
+            return Ok(None);
+        }
+
+        tokio::fs::create_dir_all(dir).await?;
+        let path = dir.join(format!(
+            "{}-{}",
+            file_name(outfile)?,
+            Utc::now().format(TIMESTAMP_FORMAT)
+        ));
+        tokio::fs::copy(outfile, &path).await?;
+
+        for backup in self.list(outfile).await?.iter().skip(self.keep.into()) {
+            tokio::fs::remove_file(&backup.path).await?;
+        }
+
+        Ok(Some(path))
+    }
+
+    /// Backups of `outfile`, newest first
+    pub async fn list(&self, outfile: &Path) -> Result<Vec<Backup>> {
+        let Some(ref dir) = self.dir else {
+            return Ok(vec![]);
+        };
+        let prefix = format!("{}-", file_name(outfile)?);
+
+        let mut entries = match tokio::fs::read_dir(dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e).context(format!("could not read {}", dir.display())),
+        };
+
+        let mut backups = vec![];
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            if let Some(timestamp) = name.to_str().and_then(|f| f.strip_prefix(&prefix)) {
+                let backup = Backup {
+                    path: entry.path(),
+                    timestamp: timestamp.to_string(),
+                };
+                if backup.taken().is_some() {
+                    backups.push(backup);
+                }
+            }
+        }
+        backups.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+
+        Ok(backups)
+    }
+
+    /// The newest backup of `outfile` or, given a timestamp (or the start of
+    /// one), the backup it identifies
+    pub async fn find(&self, outfile: &Path, timestamp: Option<&str>) -> Result<Backup> {
+        let backups = self.list(outfile).await?;
+        let mut matches = backups
+            .into_iter()
+            .filter(|f| timestamp.map_or(true, |timestamp| f.timestamp.starts_with(timestamp)))
+            .collect_vec();
+
+        match (timestamp, matches.len()) {
+            (None, 0) => bail!("there are no backups of {}", outfile.display()),
+            (Some(timestamp), 0) => bail!(
+                "there is no backup of {} taken at {timestamp}",
+                outfile.display()
+            ),
+            (Some(timestamp), count) if count > 1 => bail!(
+                "{timestamp} matches {count} backups: {}",
+                matches.iter().map(|f| &f.timestamp).join(", ")
+            ),
+            _ => Ok(matches.remove(0)),
+        }
+    }
+}
+
+fn file_name(outfile: &Path) -> Result<&str> {
+    outfile
+        .file_name()
+        .and_then(|f| f.to_str())
+        .with_context(|| format!("{} is not a file name", outfile.display()))
+}
@@ -0,0 +1,90 @@
+use std::{collections::HashSet, fmt::Display};
+
+/// Changes between two mirrorlists
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Diff {
+    /// Servers that are only in the new mirrorlist
+    pub added: Vec<String>,
+    /// Servers that are only in the old mirrorlist
+    pub removed: Vec<String>,
+    /// Servers in both mirrorlists whose rank changed, with their old and new
+    /// (1-based) positions
+    pub reordered: Vec<(String, usize, usize)>,
+}
+
+impl Diff {
+    pub fn new(old: &[String], new: &[String]) -> Self {
+        let old_set: HashSet<_> = old.iter().collect();
+        let new_set: HashSet<_> = new.iter().collect();
+
+        let added = new
+            .iter()
+            .filter(|f| !old_set.contains(f))
+            .cloned()
+            .collect();
+        let removed = old
+            .iter()
+            .filter(|f| !new_set.contains(f))
+            .cloned()
+            .collect();
+
+        // only the relative order of the servers in both lists counts, so
+        // adding or removing a server does not move everything after it
+        let kept_old: Vec<_> = old.iter().filter(|f| new_set.contains(f)).collect();
+        let kept_new: Vec<_> = new.iter().filter(|f| old_set.contains(f)).collect();
+        let position = |list: &[String], server: &String| {
+            list.iter().position(|f| f == server).map_or(0, |f| f + 1)
+        };
+        let reordered = kept_new
+            .iter()
+            .zip(kept_old.iter())
+            .filter(|(new, old)| new != old)
+            .map(|(server, _)| {
+                (
+                    server.to_string(),
+                    position(old, server),
+                    position(new, server),
+                )
+            })
+            .collect();
+
+        Self {
+            added,
+            removed,
+            reordered,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.reordered.is_empty()
+    }
+
+    pub fn summary(&self) -> String {
+        if self.is_empty() {
+            String::from("no changes")
+        } else {
+            format!(
+                "{} added, {} removed, {} reordered",
+                self.added.len(),
+                self.removed.len(),
+                self.reordered.len()
+            )
+        }
+    }
+}
+
+impl Display for Diff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.summary())?;
+        for server in &self.added {
+            write!(f, "\n+ {server}")?;
+        }
+        for server in &self.removed {
+            write!(f, "\n- {server}")?;
+        }
+        for (server, from, to) in &self.reordered {
+            write!(f, "\n~ {server} ({from} -> {to})")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use archlinux::chrono::{DateTime, Utc};
+use itertools::Itertools;
+
+use crate::{cli::Protocol, config::Configuration};
+
+/// How an exported mirrorlist came to be
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub source: String,
+    /// When the source last checked the mirrors
+    pub last_check: Option<DateTime<Utc>>,
+    pub filters: String,
+    pub sort: String,
+}
+
+impl From<&Configuration> for Header {
+    fn from(config: &Configuration) -> Self {
+        let mut filters = vec![format!(
+            "protocols {}",
+            config
+                .filters
+                .iter()
+                .filter(|f| **f != Protocol::InSync)
+                .join(", ")
+        )];
+        filters.push(if config.country.is_empty() {
+            String::from("all countries")
+        } else {
+            format!("countries {}", config.country.join(", "))
+        });
+        filters.push(format!("completion >= {}%", config.completion_percent));
+        if config.age != 0 {
+            filters.push(format!("synced within {}h", config.age));
+        }
+        if config.one_per_host {
+            filters.push(String::from("one per host"));
+        }
+        let rules = &config.rules;
+        if !rules.country_codes.is_empty() {
+            filters.push(format!("country codes {}", rules.country_codes.join(", ")));
+        }
+        if !rules.exclude_country_codes.is_empty() {
+            filters.push(format!(
+                "excluding country codes {}",
+                rules.exclude_country_codes.join(", ")
+            ));
+        }
+        if !rules.include_only.is_empty() {
+            filters.push(format!("only {}", rules.include_only.iter().join(", ")));
+        }
+        if !rules.exclude.is_empty() {
+            filters.push(format!("excluding {}", rules.exclude.iter().join(", ")));
+        }
+        if let Some(latest) = config.stages.latest {
+            filters.push(format!("latest {latest}"));
+        }
+        if let Some(score) = config.stages.score {
+            filters.push(format!("best {score} by score"));
+        }
+        if let Some(fastest) = config.stages.fastest {
+            filters.push(format!("fastest {fastest}"));
+        }
+        if let Some(max) = config.quota.max_per_country {
+            filters.push(format!("at most {max} per country"));
+        }
+        if let Some(min) = config.quota.min_countries {
+            filters.push(format!("at least {min} countries"));
+        }
+
+        let sort = if config.rate || config.stages.fastest.is_some() {
+            String::from("download rate")
+        } else {
+            config.sort.to_string()
+        };
+
+        Self {
+            source: config.url.clone(),
+            last_check: None,
+            filters: filters.join("; "),
+            sort,
+        }
+    }
+}
+
+impl Header {
+    pub fn render(&self) -> String {
+        let format = "%Y-%m-%d %H:%M:%S UTC";
+        let mut lines = vec![
+            format!(
+                "# Generated by mirro-rs {} on {}",
+                env!("CARGO_PKG_VERSION"),
+                Utc::now().format(format)
+            ),
+            format!("# Source:     {}", self.source),
+        ];
+        if let Some(last_check) = self.last_check {
+            lines.push(format!("# Last check: {}", last_check.format(format)));
+        }
+        lines.push(format!("# Filters:    {}", self.filters));
+        lines.push(format!("# Sort:       {}", self.sort));
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,65 @@
+use anyhow::{bail, Result};
+
+use crate::cli::Placement;
+
+pub const BEGIN: &str = "# BEGIN mirro-rs";
+pub const END: &str = "# END mirro-rs";
+
+/// The lines of a mirrorlist before, inside and after the managed block
+struct Sections<'a> {
+    before: Vec<&'a str>,
+    block: Vec<&'a str>,
+    after: Vec<&'a str>,
+}
+
+fn split(contents: &str) -> Result<Option<Sections<'_>>> {
+    let lines: Vec<_> = contents.lines().collect();
+    let begin = lines.iter().position(|f| f.trim() == BEGIN);
+    let end = lines.iter().position(|f| f.trim() == END);
+
+    match (begin, end) {
+        (None, None) => Ok(None),
+        (Some(begin), Some(end)) if begin < end => Ok(Some(Sections {
+            before: lines[..begin].to_vec(),
+            block: lines[begin + 1..end].to_vec(),
+            after: lines[end + 1..].to_vec(),
+        })),
+        _ => bail!("the outfile has a \"{BEGIN}\" line without a matching \"{END}\" line, or the other way round"),
+    }
+}
+
+/// The part of a mirrorlist that mirro-rs manages: the lines between the
+/// markers, or the whole file if it has none yet
+pub fn block(contents: &str) -> Result<String> {
+    Ok(split(contents)?
+        .map(|sections| sections.block.join("\n"))
+        .unwrap_or_else(|| contents.trim_end_matches('\n').to_string()))
+}
+
+/// Replaces the managed block of `current` with `servers`. A file without
+/// markers is adopted whole. User lines on one side of the block are laid out
+/// according to `placement`, while a block with user lines on both sides is
+/// replaced where it is
+pub fn splice(current: Option<&str>, servers: &str, placement: Placement) -> Result<String> {
+    let block = format!("{BEGIN}\n{servers}\n{END}\n");
+    let Some(sections) = current.map(split).transpose()?.flatten() else {
+        return Ok(block);
+    };
+
+    let lines = |lines: &[&str]| lines.iter().map(|f| format!("{f}\n")).collect::<String>();
+    Ok(
+        if !sections.before.is_empty() && !sections.after.is_empty() {
+            format!(
+                "{}{block}{}",
+                lines(&sections.before),
+                lines(&sections.after)
+            )
+        } else {
+            let user_lines = lines(&[sections.before, sections.after].concat());
+            match placement {
+                Placement::Before => format!("{block}{user_lines}"),
+                Placement::After => format!("{user_lines}{block}"),
+            }
+        },
+    )
+}
@@ -0,0 +1,149 @@
+pub mod backup;
+mod diff;
+mod header;
+pub mod managed;
+mod quota;
+pub mod write;
+
+use std::{path::PathBuf, time::Duration};
+
+use archlinux::{Country, Mirror};
+
+pub use backup::Backups;
+pub use diff::Diff;
+pub use header::Header;
+pub use quota::Quota;
+
+use crate::{cli::Placement, config::Configuration};
+
+/// Where and how an exported mirrorlist is written
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub outfile: PathBuf,
+    /// How many of the selected mirrors to keep
+    pub count: usize,
+    pub quota: Quota,
+    pub preview: Preview,
+    pub backups: Backups,
+    pub placement: Placement,
+    /// Write the mirrorlist even if no mirrors were selected
+    pub force: bool,
+    /// Treat partial failures, such as a mirror that could not be rated, as fatal
+    pub strict: bool,
+    /// Describes how the mirrorlist was generated, `None` if disabled
+    pub header: Option<Header>,
+    /// Describe each server in a comment above it
+    pub comments: bool,
+}
+
+impl From<&Configuration> for Output {
+    fn from(config: &Configuration) -> Self {
+        Self {
+            outfile: config.outfile.clone(),
+            count: config.export.into(),
+            quota: config.quota,
+            preview: config.preview,
+            backups: config.backups.clone(),
+            force: config.force,
+            strict: config.strict,
+            placement: config.placement,
+            header: config.header.then(|| Header::from(config)),
+            comments: config.comments,
+        }
+    }
+}
+
+/// Ways of reviewing a mirrorlist before it is written
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Preview {
+    /// Print the mirrorlist instead of writing it
+    pub dry_run: bool,
+    /// Compare the mirrorlist with the contents of the outfile
+    pub diff: bool,
+}
+
+/// A mirror to export, along with what is known about it
+#[derive(Debug, Default, Clone)]
+pub struct Entry {
+    pub url: String,
+    pub country: Option<String>,
+    pub protocol: Option<archlinux::Protocol>,
+    pub score: Option<f64>,
+    pub delay: Option<i64>,
+    /// How long downloading the rating file took
+    pub rate: Option<Duration>,
+}
+
+impl Entry {
+    pub fn new(country: &Country, mirror: &Mirror) -> Self {
+        Self {
+            url: mirror.url.clone(),
+            country: Some(country.name.clone()),
+            protocol: Some(mirror.protocol),
+            score: mirror.score,
+            delay: mirror.delay,
+            rate: None,
+        }
+    }
+
+    pub fn server(&self) -> String {
+        format!("{}$repo/os/$arch", self.url)
+    }
+
+    /// What is known about the mirror, if anything
+    fn comment(&self) -> Option<String> {
+        let mut parts = vec![];
+        parts.extend(self.country.clone());
+        parts.extend(self.protocol.map(|f| f.to_string()));
+        parts.extend(self.score.map(|f| format!("score {f:.2}")));
+        parts.extend(self.delay.map(|f| {
+            let (hours, minutes) = (f / 3600, (f / 60) % 60);
+            format!("delay {hours}h {minutes}m")
+        }));
+        parts.extend(self.rate.map(|f| format!("rated {:.3}s", f.as_secs_f64())));
+
+        (!parts.is_empty()).then(|| format!("# {}", parts.join(", ")))
+    }
+}
+
+/// Included URLs are only known by their URL
+impl From<String> for Entry {
+    fn from(url: String) -> Self {
+        Self {
+            url,
+            ..Default::default()
+        }
+    }
+}
+
+/// The `Server` values for the given mirrors
+pub fn servers(entries: &[Entry]) -> Vec<String> {
+    entries.iter().map(Entry::server).collect()
+}
+
+/// The contents of the managed block: the header (if any) followed by a
+/// `Server` line, optionally commented, for each mirror
+pub fn render(entries: &[Entry], header: Option<&Header>, comments: bool) -> String {
+    let mut lines = vec![];
+    if let Some(header) = header {
+        lines.push(header.render());
+    }
+    for entry in entries {
+        if comments {
+            lines.extend(entry.comment());
+        }
+        lines.push(format!("Server = {}", entry.server()));
+    }
+    lines.join("\n")
+}
+
+/// The `Server` values in a mirrorlist, ignoring commented out servers
+pub fn parse(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.trim().split_once('=')?;
+            (key.trim() == "Server").then(|| value.trim().to_string())
+        })
+        .collect()
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use tracing::warn;
+
+use super::Entry;
+
+/// How the exported mirrors are spread across countries, so that a single
+/// regional outage does not take out every server
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    /// Mirrors from a country past the first n are left out
+    pub max_per_country: Option<u16>,
+    /// Swap mirrors from the countries that have several for the best mirror
+    /// of another country until this many are represented
+    pub min_countries: Option<u16>,
+}
+
+impl Quota {
+    /// The first `count` entries that keep to the quota, in the order they
+    /// were given. Entries without a country, such as included URLs, are
+    /// never left out or swapped
+    pub fn apply(&self, entries: &[Entry], count: usize) -> Vec<Entry> {
+        let mut per_country: HashMap<&str, u16> = HashMap::new();
+        let eligible = entries
+            .iter()
+            .enumerate()
+            .filter(
+                |(_, entry)| match (entry.country.as_deref(), self.max_per_country) {
+                    (Some(country), Some(max)) => {
+                        let taken = per_country.entry(country).or_default();
+                        *taken += 1;
+                        *taken <= max
+                    }
+                    _ => true,
+                },
+            )
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>();
+
+        let (chosen, rest) = eligible.split_at(count.min(eligible.len()));
+        let mut chosen = chosen.to_vec();
+
+        if let Some(min) = self.min_countries {
+            let mut represented: HashMap<&str, usize> = HashMap::new();
+            for country in chosen.iter().filter_map(|f| entries[*f].country.as_deref()) {
+                *represented.entry(country).or_default() += 1;
+            }
+
+            for idx in rest {
+                if represented.len() >= min.into() {
+                    break;
+                }
+                let Some(country) = entries[*idx].country.as_deref() else {
+                    continue;
+                };
+                if represented.contains_key(country) {
+                    continue;
+                }
+                // the worst mirror of a country that would still be represented
+                let Some(pos) = chosen.iter().rposition(|f| {
+                    entries[*f]
+                        .country
+                        .as_deref()
+                        .is_some_and(|f| represented[f] > 1)
+                }) else {
+                    break;
+                };
+                let replaced = entries[chosen[pos]].country.as_deref().unwrap_or_default();
+                *represented.get_mut(replaced).unwrap() -= 1;
+                represented.insert(country, 1);
+                chosen[pos] = *idx;
+            }
+
+            if represented.len() < min.into() {
+                warn!(
+                    countries = represented.len(),
+                    "the exported mirrors are from fewer than {min} countries"
+                );
+            }
+            chosen.sort_unstable();
+        }
+
+        chosen.into_iter().map(|f| entries[f].clone()).collect()
+    }
+}
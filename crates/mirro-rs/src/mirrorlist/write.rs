@@ -0,0 +1,97 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+
+/// Replaces `path` with `contents` without ever leaving a partially written
+/// file behind: the contents go to a temporary file in the same directory,
+/// which is synced and then renamed over `path`. The mode, owner and SELinux
+/// context of an existing `path` are carried over
+pub async fn atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let temp = temp_path(path)?;
+    let result = write_temp(path, &temp, contents).await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&temp).await;
+    }
+    result
+}
+
+async fn write_temp(path: &Path, temp: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(temp)
+        .await?;
+    file.write_all(contents).await?;
+
+    match tokio::fs::metadata(path).await {
+        Ok(metadata) => {
+            file.set_permissions(metadata.permissions()).await?;
+            copy_attributes(path, temp, &metadata);
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    file.sync_all().await?;
+    drop(file);
+
+    tokio::fs::rename(temp, path).await?;
+
+    // make the rename itself durable
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|f| !f.as_os_str().is_empty()) {
+        tokio::fs::File::open(dir).await?.sync_all().await?;
+    }
+
+    Ok(())
+}
+
+fn temp_path(path: &Path) -> std::io::Result<PathBuf> {
+    let name = path.file_name().ok_or_else(|| {
+        std::io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} is not a file name", path.display()),
+        )
+    })?;
+    let mut temp = std::ffi::OsString::from(".");
+    temp.push(name);
+    temp.push(format!(".{}.tmp", std::process::id()));
+    Ok(path.with_file_name(temp))
+}
+
+/// Owner and SELinux context. Failing to carry them over is not fatal, as
+/// only privileged users can give files away
+#[cfg(unix)]
+fn copy_attributes(path: &Path, temp: &Path, metadata: &std::fs::Metadata) {
+    use std::os::unix::fs::MetadataExt;
+
+    if let Ok(current) = std::fs::metadata(temp) {
+        if current.uid() != metadata.uid() || current.gid() != metadata.gid() {
+            if let Err(e) =
+                std::os::unix::fs::chown(temp, Some(metadata.uid()), Some(metadata.gid()))
+            {
+                warn!("could not keep the owner of {}: {e}", path.display());
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        const SELINUX: &str = "security.selinux";
+        if let Ok(Some(context)) = xattr::get(path, SELINUX) {
+            if let Err(e) = xattr::set(temp, SELINUX, &context) {
+                warn!(
+                    "could not keep the SELinux context of {}: {e}",
+                    path.display()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn copy_attributes(_path: &Path, _temp: &Path, _metadata: &std::fs::Metadata) {}
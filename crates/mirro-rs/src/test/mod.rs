@@ -1,11 +1,984 @@
-use crate::{cli::ArgConfig, config::Configuration, direct::begin};
+use crate::{
+    cli::{ArgConfig, Placement, Protocol},
+    config::Configuration,
+    direct::begin,
+    mirrorlist::{self, managed, Backups, Diff, Entry},
+};
 
 #[tokio::test]
 async fn sample_bin() {
     let config_str = include_str!("../../../../examples/mirro-rs.toml");
-    let configuration: ArgConfig = toml::from_str(config_str).unwrap();
+    let mut configuration: ArgConfig = toml::from_str(config_str).unwrap();
+    // rating needs the mirrors to be reachable, which they may not be here
+    configuration.general.rate = Some(false);
     let config = Configuration::from(configuration);
     let result = begin(config).await;
     dbg!(&result);
     assert!(result.is_ok());
 }
+
+#[test]
+fn mirrorlist_diff() {
+    let old = mirrorlist::parse(
+        "Server = https://a/$repo/os/$arch\n#Server = https://b/$repo/os/$arch\nServer = https://c/$repo/os/$arch\nServer = https://d/$repo/os/$arch",
+    );
+    let new = mirrorlist::servers(&[
+        Entry::from(String::from("https://d/")),
+        Entry::from(String::from("https://e/")),
+        Entry::from(String::from("https://c/")),
+    ]);
+    let diff = Diff::new(&old, &new);
+
+    assert_eq!(diff.added, vec!["https://e/$repo/os/$arch"]);
+    assert_eq!(diff.removed, vec!["https://a/$repo/os/$arch"]);
+    assert_eq!(
+        diff.reordered,
+        vec![
+            (String::from("https://d/$repo/os/$arch"), 3, 1),
+            (String::from("https://c/$repo/os/$arch"), 2, 3),
+        ]
+    );
+    assert!(Diff::new(&new, &new).is_empty());
+}
+
+#[tokio::test]
+async fn rotate_backups() {
+    let dir = std::env::temp_dir().join(format!("mirro-rs-backups-{}", std::process::id()));
+    let outfile = dir.join("mirrorlist");
+    let backups = Backups {
+        dir: Some(dir.join("backups")),
+        keep: 2,
+    };
+
+    assert!(backups.create(&outfile).await.unwrap().is_none());
+
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+    for contents in ["first", "second", "third"] {
+        tokio::fs::write(&outfile, contents).await.unwrap();
+        assert!(backups.create(&outfile).await.unwrap().is_some());
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    }
+
+    let kept = backups.list(&outfile).await.unwrap();
+    assert_eq!(kept.len(), 2);
+
+    let newest = backups.find(&outfile, None).await.unwrap();
+    assert_eq!(
+        tokio::fs::read_to_string(newest.path).await.unwrap(),
+        "third"
+    );
+
+    let oldest = backups
+        .find(&outfile, Some(&kept[1].timestamp))
+        .await
+        .unwrap();
+    assert_eq!(
+        tokio::fs::read_to_string(oldest.path).await.unwrap(),
+        "second"
+    );
+    assert!(backups.find(&outfile, Some("1999")).await.is_err());
+
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn atomic_write_keeps_mode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!("mirro-rs-write-{}", std::process::id()));
+    let outfile = dir.join("mirrorlist");
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+    tokio::fs::write(&outfile, "old").await.unwrap();
+    tokio::fs::set_permissions(&outfile, std::fs::Permissions::from_mode(0o640))
+        .await
+        .unwrap();
+
+    mirrorlist::write::atomic(&outfile, b"new").await.unwrap();
+
+    assert_eq!(tokio::fs::read_to_string(&outfile).await.unwrap(), "new");
+    let mode = tokio::fs::metadata(&outfile)
+        .await
+        .unwrap()
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o777, 0o640);
+    let mut entries = tokio::fs::read_dir(&dir).await.unwrap();
+    let mut count = 0;
+    while entries.next_entry().await.unwrap().is_some() {
+        count += 1;
+    }
+    assert_eq!(count, 1, "the temporary file was left behind");
+
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+}
+
+#[test]
+fn managed_block() {
+    let servers = "Server = https://new/$repo/os/$arch";
+
+    let block = "# BEGIN mirro-rs\nServer = https://new/$repo/os/$arch\n# END mirro-rs\n";
+    assert_eq!(
+        managed::splice(None, servers, Placement::Before).unwrap(),
+        block
+    );
+
+    // a file without markers is adopted whole
+    let previous = "# Generated by reflector\nServer = https://old/$repo/os/$arch\n";
+    for placement in [Placement::Before, Placement::After] {
+        assert_eq!(
+            managed::splice(Some(previous), servers, placement).unwrap(),
+            block
+        );
+    }
+    assert_eq!(
+        managed::block(previous).unwrap(),
+        "# Generated by reflector\nServer = https://old/$repo/os/$arch"
+    );
+
+    // user lines on one side of the block go on the side asked for
+    let lan = "# LAN cache\nServer = http://lan/$repo/os/$arch\n";
+    let current = format!("{lan}{block}");
+    assert_eq!(
+        managed::splice(Some(&current), servers, Placement::Before).unwrap(),
+        format!("{block}{lan}")
+    );
+    assert_eq!(
+        managed::splice(Some(&current), servers, Placement::After).unwrap(),
+        format!("{lan}{block}")
+    );
+
+    // a block with user lines on both sides is replaced where it is
+    let fallback = "# fallback\nServer = http://fallback/$repo/os/$arch\n";
+    let current = format!(
+        "{lan}# BEGIN mirro-rs\nServer = https://old/$repo/os/$arch\n# END mirro-rs\n{fallback}"
+    );
+    assert_eq!(
+        managed::block(&current).unwrap(),
+        "Server = https://old/$repo/os/$arch"
+    );
+    for placement in [Placement::Before, Placement::After] {
+        assert_eq!(
+            managed::splice(Some(&current), servers, placement).unwrap(),
+            format!("{lan}{block}{fallback}")
+        );
+    }
+    assert!(managed::splice(Some("# BEGIN mirro-rs\n"), servers, Placement::After).is_err());
+}
+
+#[test]
+fn render_comments() {
+    let entries = [
+        Entry {
+            url: String::from("https://a/"),
+            country: Some(String::from("Germany")),
+            protocol: Some(archlinux::Protocol::Https),
+            score: Some(1.234),
+            delay: Some(3720),
+            rate: Some(std::time::Duration::from_millis(250)),
+        },
+        Entry::from(String::from("https://b/")),
+    ];
+
+    assert_eq!(
+        mirrorlist::render(&entries, None, true),
+        "# Germany, https, score 1.23, delay 1h 2m, rated 0.250s\nServer = https://a/$repo/os/$arch\nServer = https://b/$repo/os/$arch"
+    );
+    assert_eq!(
+        mirrorlist::render(&entries, None, false),
+        "Server = https://a/$repo/os/$arch\nServer = https://b/$repo/os/$arch"
+    );
+}
+
+#[test]
+fn reflector_options() {
+    use clap::Parser;
+
+    use crate::commands::reflector::ReflectorArgs;
+
+    let args = ReflectorArgs::parse_from([
+        "reflector",
+        "--latest",
+        "20",
+        "--protocol",
+        "https",
+        "--country",
+        "Germany,France",
+        "--age",
+        "12.5",
+        "--completion-percent",
+        "95",
+        "--sort",
+        "rate",
+        "--save",
+        "/tmp/mirrorlist",
+    ]);
+    let config = Configuration::from(&args);
+
+    assert_eq!(config.outfile, std::path::PathBuf::from("/tmp/mirrorlist"));
+    assert_eq!(config.filters, vec![Protocol::Https]);
+    assert_eq!(config.country, vec!["Germany", "France"]);
+    assert_eq!(config.age, 13);
+    assert_eq!(config.completion_percent, 95);
+    assert!(!config.preview.dry_run);
+
+    let args = ReflectorArgs::parse_from(["reflector"]);
+    assert!(Configuration::from(&args).preview.dry_run);
+
+    let args = ReflectorArgs::parse_from(["reflector", "--age", "0"]);
+    assert_eq!(Configuration::from(&args).age, 0);
+}
+
+#[tokio::test]
+async fn selection_stages() {
+    use crate::direct::stages::{Candidate, Stages};
+
+    let mirrorlist =
+        archlinux::parse_local(include_str!("../../../archlinux/sample/archlinux.json")).unwrap();
+    let mirrors = mirrorlist
+        .countries
+        .iter()
+        .flat_map(|country| country.mirrors.iter().map(move |f| (country, f)))
+        .collect::<Vec<_>>();
+    let client = archlinux::get_client(None).unwrap();
+
+    let mut candidates = mirrors.iter().copied().map(Candidate::new).collect();
+    Stages {
+        latest: Some(20),
+        score: Some(5),
+        fastest: None,
+    }
+    .run(&mut candidates, &client, None, true)
+    .await
+    .unwrap();
+
+    let mut latest = mirrors.clone();
+    latest.sort_by_key(|(_, f)| std::cmp::Reverse(f.last_sync));
+    let latest = &latest[..20];
+
+    assert_eq!(candidates.len(), 5);
+    assert!(candidates
+        .iter()
+        .all(|f| latest.iter().any(|(_, mirror)| mirror.url == f.mirror.url)));
+    assert!(candidates
+        .windows(2)
+        .all(|f| f[0].mirror.score.unwrap_or(f64::MAX) <= f[1].mirror.score.unwrap_or(f64::MAX)));
+    assert!(candidates.iter().all(|f| f.rate.is_none()));
+}
+
+#[test]
+fn export_sort() {
+    use crate::{cli::SortSpec, direct::stages::Candidate};
+
+    let mirrorlist =
+        archlinux::parse_local(include_str!("../../../archlinux/sample/archlinux.json")).unwrap();
+    let mut candidates = mirrorlist
+        .countries
+        .iter()
+        .flat_map(|country| country.mirrors.iter().map(move |f| (country, f)))
+        .map(Candidate::new)
+        .collect::<Vec<_>>();
+    let sort = |spec: &str, candidates: &mut Vec<Candidate>| {
+        spec.parse::<SortSpec>().unwrap().sort(candidates);
+    };
+
+    sort("-completion", &mut candidates);
+    assert!(candidates
+        .windows(2)
+        .all(|f| f[0].mirror.completion_pct >= f[1].mirror.completion_pct));
+
+    sort("delay", &mut candidates);
+    assert!(candidates.windows(2).all(|f| {
+        f[0].mirror.delay.unwrap_or(i64::MAX) <= f[1].mirror.delay.unwrap_or(i64::MAX)
+    }));
+    assert!(candidates.last().unwrap().mirror.delay.is_none());
+
+    sort("duration", &mut candidates);
+    assert!(candidates.windows(2).all(|f| {
+        f[0].mirror.duration_stddev.unwrap_or(f64::MAX)
+            <= f[1].mirror.duration_stddev.unwrap_or(f64::MAX)
+    }));
+
+    sort("score", &mut candidates);
+    assert!(candidates
+        .windows(2)
+        .all(|f| f[0].mirror.score.unwrap_or(f64::MAX) <= f[1].mirror.score.unwrap_or(f64::MAX)));
+
+    // descending keys still leave the mirrors without a value last
+    sort("-delay", &mut candidates);
+    assert!(candidates.windows(2).all(|f| {
+        f[0].mirror.delay.unwrap_or(i64::MIN) >= f[1].mirror.delay.unwrap_or(i64::MIN)
+            || f[1].mirror.delay.is_none()
+    }));
+    assert!(candidates.last().unwrap().mirror.delay.is_none());
+
+    // ties on the first key are broken by the next, then by URL
+    sort("-completion,delay", &mut candidates);
+    assert!(candidates.windows(2).all(|f| {
+        let (a, b) = (f[0].mirror, f[1].mirror);
+        a.completion_pct > b.completion_pct
+            || (a.completion_pct == b.completion_pct
+                && (a.delay.unwrap_or(i64::MAX), &a.url) <= (b.delay.unwrap_or(i64::MAX), &b.url))
+    }));
+
+    let urls = |candidates: &[Candidate]| {
+        candidates
+            .iter()
+            .map(|f| f.mirror.url.clone())
+            .collect::<Vec<_>>()
+    };
+    sort("-completion", &mut candidates);
+    let sorted = urls(&candidates);
+    candidates.reverse();
+    sort("-completion", &mut candidates);
+    assert_eq!(urls(&candidates), sorted);
+}
+
+#[test]
+fn country_quota() {
+    use crate::mirrorlist::{Entry, Quota};
+
+    let entry = |url: &str, country: Option<&str>| Entry {
+        url: url.to_string(),
+        country: country.map(String::from),
+        ..Default::default()
+    };
+    let entries = vec![
+        entry("a1", Some("A")),
+        entry("a2", Some("A")),
+        entry("a3", Some("A")),
+        entry("b1", Some("B")),
+        entry("a4", Some("A")),
+        entry("b2", Some("B")),
+        entry("c1", Some("C")),
+        entry("d1", Some("D")),
+        entry("included", None),
+    ];
+    let urls = |entries: Vec<Entry>| entries.into_iter().map(|f| f.url).collect::<Vec<_>>();
+
+    assert_eq!(
+        urls(Quota::default().apply(&entries, 4)),
+        ["a1", "a2", "a3", "b1"]
+    );
+
+    let quota = Quota {
+        max_per_country: Some(2),
+        min_countries: None,
+    };
+    assert_eq!(urls(quota.apply(&entries, 4)), ["a1", "a2", "b1", "b2"]);
+    assert_eq!(
+        urls(quota.apply(&entries, 10)),
+        ["a1", "a2", "b1", "b2", "c1", "d1", "included"]
+    );
+
+    // the worst mirrors of the countries with the most are swapped out, and
+    // the order is kept
+    let quota = Quota {
+        max_per_country: None,
+        min_countries: Some(3),
+    };
+    assert_eq!(urls(quota.apply(&entries, 4)), ["a1", "a2", "b1", "c1"]);
+    let quota = Quota {
+        max_per_country: Some(2),
+        min_countries: Some(4),
+    };
+    assert_eq!(urls(quota.apply(&entries, 4)), ["a1", "b1", "c1", "d1"]);
+
+    // fewer countries than wanted is not an error
+    let quota = Quota {
+        max_per_country: None,
+        min_countries: Some(10),
+    };
+    assert_eq!(
+        urls(quota.apply(&entries, 5)),
+        ["a1", "a2", "b1", "c1", "d1"]
+    );
+}
+
+#[test]
+fn sort_spec() {
+    use clap::Parser;
+
+    use crate::cli::{SelectionSort, SortKey, SortSpec};
+
+    let spec = "score, delay,-completion".parse::<SortSpec>().unwrap();
+    assert_eq!(
+        spec.0,
+        vec![
+            SortKey {
+                field: SelectionSort::Score,
+                descending: false
+            },
+            SortKey {
+                field: SelectionSort::Delay,
+                descending: false
+            },
+            SortKey {
+                field: SelectionSort::Percentage,
+                descending: true
+            },
+        ]
+    );
+    assert_eq!(spec.to_string(), "score,delay,-completion");
+    // the old single key name keeps sorting the most complete mirrors first
+    assert_eq!(
+        "percentage".parse::<SortSpec>().unwrap().to_string(),
+        "-completion"
+    );
+    assert!("score,speed".parse::<SortSpec>().is_err());
+    assert!("score,-score".parse::<SortSpec>().is_err());
+    assert!("".parse::<SortSpec>().is_err());
+
+    // descending keys can follow the flag as a separate argument
+    let args = ArgConfig::try_parse_from(["mirro-rs", "--sort", "-completion,score"]).unwrap();
+    assert_eq!(args.general.sort.unwrap().to_string(), "-completion,score");
+
+    let mut spec = SortSpec::default();
+    spec.toggle(SelectionSort::Percentage);
+    assert_eq!(spec.to_string(), "score,-completion");
+    spec.toggle(SelectionSort::Percentage);
+    assert_eq!(spec.to_string(), "score,completion");
+    spec.toggle(SelectionSort::Percentage);
+    assert_eq!(spec.to_string(), "score");
+    spec.toggle(SelectionSort::Score);
+    assert_eq!(spec.to_string(), "-score");
+    spec.toggle(SelectionSort::Score);
+    assert_eq!(spec.to_string(), "score");
+}
+
+#[test]
+fn filter_rules() {
+    use crate::config::rules::{Pattern, Rules};
+
+    let mirrorlist =
+        archlinux::parse_local(include_str!("../../../archlinux/sample/archlinux.json")).unwrap();
+    let country = |code: &str| {
+        mirrorlist
+            .countries
+            .iter()
+            .find(|f| f.code == code)
+            .unwrap()
+    };
+    let germany = country("DE");
+    let mirror = germany
+        .mirrors
+        .iter()
+        .find(|f| f.url.starts_with("https://"))
+        .unwrap();
+    let host = mirror.host().unwrap();
+
+    assert!(Pattern::new("*.example.org")
+        .unwrap()
+        .is_match(&archlinux::Mirror {
+            url: String::from("https://mirror.EXAMPLE.org/arch/"),
+            ..mirror.clone()
+        }));
+    assert!(Pattern::new(host).unwrap().is_match(mirror));
+    assert!(
+        Pattern::new(&format!("re:^https://{}", regex::escape(host)))
+            .unwrap()
+            .is_match(mirror)
+    );
+    assert!(!Pattern::new("http://*").unwrap().is_match(mirror));
+    assert!(Pattern::new("re:(").is_err());
+
+    let rules = |exclude: &[&str], include_only: &[&str], codes: &[&str], excluded: &[&str]| {
+        let owned = |f: &[&str]| f.iter().map(|f| f.to_string()).collect();
+        Rules::new(
+            owned(exclude),
+            owned(include_only),
+            owned(codes),
+            owned(excluded),
+        )
+    };
+    assert!(Rules::default().allows(germany, mirror));
+    assert!(!rules(&[host], &[], &[], &[]).allows(germany, mirror));
+    assert!(rules(&[], &["https://*"], &[], &[]).allows(germany, mirror));
+    assert!(!rules(&[], &["ftp://*"], &[], &[]).allows(germany, mirror));
+    assert!(rules(&[], &[], &["de", "FR"], &[]).allows(germany, mirror));
+    assert!(!rules(&[], &[], &["FR"], &[]).allows(germany, mirror));
+    assert!(!rules(&[], &[], &[], &["DE"]).allows(germany, mirror));
+}
+
+#[test]
+fn country_matching() {
+    use crate::config::countries;
+
+    let mirrorlist =
+        archlinux::parse_local(include_str!("../../../archlinux/sample/archlinux.json")).unwrap();
+    let country = |code: &str| {
+        mirrorlist
+            .countries
+            .iter()
+            .find(|f| f.code == code)
+            .unwrap()
+    };
+
+    for (wanted, code) in [
+        ("DE", "DE"),
+        ("de", "DE"),
+        ("germany", "DE"),
+        ("UK", "GB"),
+        ("United Kingdom", "GB"),
+        ("USA", "US"),
+        ("United States of America", "US"),
+        ("north-macedonia", "MK"),
+    ] {
+        assert!(countries::matches(wanted, country(code)), "{wanted}");
+    }
+    assert!(!countries::matches("UK", country("UA")));
+    assert!(!countries::matches("", country("DE")));
+
+    let wanted = |f: &[&str]| f.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+    assert!(countries::check(&mirrorlist.countries, &wanted(&["DE", "UK", "France"])).is_ok());
+    let err = countries::check(&mirrorlist.countries, &wanted(&["DE", "Germny"]))
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("unknown country \"Germny\", did you mean Germany (DE)"));
+}
+
+#[test]
+fn config_validation() {
+    use crate::config::validate::{self, Issue};
+
+    let sparse: ArgConfig = toml::from_str("[general]\nrate-speed = true\n").unwrap();
+    assert!(validate::problems(&sparse.general, &sparse.filters).is_empty());
+
+    let config: ArgConfig = toml::from_str(
+        r#"
+[general]
+outfile = "/tmp/"
+export = 0
+prefer = [ "https", "ipv4" ]
+
+[filters]
+completion-percent = 101
+protocols = []
+exclude = [ "re:(", "*.example.org" ]
+exclude-country-codes = [ "USA", "de" ]
+"#,
+    )
+    .unwrap();
+    let problems = validate::problems(&config.general, &config.filters);
+    assert_eq!(
+        problems.iter().map(|f| f.key).collect::<Vec<_>>(),
+        vec![
+            "outfile",
+            "export",
+            "prefer",
+            "protocols",
+            "completion-percent",
+            "exclude",
+            "exclude-country-codes"
+        ]
+    );
+    assert_eq!(
+        Issue::command_line(problems[5].clone()).to_string(),
+        "command line: --exclude: invalid pattern `re:(`: unclosed group"
+    );
+    assert_eq!(problems[6].flag, "exclude-country-code");
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn profiles() {
+    use crate::config::read_config_file;
+
+    let path = std::env::temp_dir().join(format!("mirro-rs-profiles-{}.toml", std::process::id()));
+    std::fs::write(
+        &path,
+        r#"
+[general]
+outfile = "/etc/pacman.d/mirrorlist"
+export = 20
+
+[filters]
+countries = [ "Germany" ]
+protocols = [ "https" ]
+
+[profiles.ci.general]
+export = 5
+
+[profiles.ci.filters]
+protocols = [ "http", "https" ]
+
+[profiles.zz]
+hostnames = [ "*" ]
+
+[profiles.zz.general]
+export = 0
+"#,
+    )
+    .unwrap();
+
+    let (config, file) = read_config_file(Some(&path), Some("ci")).unwrap();
+    let config = Configuration::from((ArgConfig::default(), config));
+    assert_eq!(config.export, 5);
+    assert_eq!(config.country, ["Germany"]);
+    assert_eq!(config.filters[..2], [Protocol::Http, Protocol::Https]);
+    assert_eq!(config.profile.as_deref(), Some("ci"));
+
+    // every profile is checked, not only the one in use
+    let issues = file.unwrap().issues();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(
+        issues[0].to_string(),
+        format!(
+            "{}:20:1: export (profile zz): must be at least 1",
+            path.display()
+        )
+    );
+
+    // picked for any host
+    let (config, _) = read_config_file(Some(&path), None).unwrap();
+    assert_eq!(config.general.profile.as_deref(), Some("zz"));
+
+    let issues = read_config_file(Some(&path), Some("server")).unwrap_err();
+    assert_eq!(
+        issues[0].message,
+        "unknown profile `server`, expected one of: ci, zz"
+    );
+
+    // `number` is another name for `export`, in either layer
+    for (base, profile) in [("export", "number"), ("number", "export")] {
+        std::fs::write(
+            &path,
+            format!("[general]\n{base} = 20\n[profiles.ci.general]\n{profile} = 5\n"),
+        )
+        .unwrap();
+        let (config, _) = read_config_file(Some(&path), Some("ci")).unwrap();
+        assert_eq!(config.general.export, Some(5), "{base} then {profile}");
+    }
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn exit_codes() {
+    use crate::{
+        errors::{self, Error},
+        mirrorlist::Output,
+        tui::io::handler::IoAsyncHandler,
+    };
+
+    let dir = std::env::temp_dir().join(format!("mirro-rs-exit-{}", std::process::id()));
+    let config = Configuration {
+        outfile: dir.join("mirrorlist"),
+        export: 10,
+        ..Default::default()
+    };
+
+    let result = IoAsyncHandler::write_to_file(Output::from(&config), &[], None, None).await;
+    assert!(matches!(result, Err(Error::NoMirrors { .. })));
+    assert_eq!(
+        errors::exit_code(&anyhow::Error::from(result.unwrap_err())),
+        errors::NO_MIRRORS
+    );
+    assert!(!dir.exists());
+
+    let blocked = std::env::temp_dir().join(format!("mirro-rs-exit-{}.file", std::process::id()));
+    tokio::fs::write(&blocked, "").await.unwrap();
+    let config = Configuration {
+        outfile: blocked.join("mirrorlist"),
+        ..config
+    };
+    let entries = [Entry::from(String::from("https://a/"))];
+    let result = IoAsyncHandler::write_to_file(Output::from(&config), &entries, None, None).await;
+    assert_eq!(result.unwrap_err().code(), errors::WRITE);
+    tokio::fs::remove_file(&blocked).await.unwrap();
+
+    let client = archlinux::ClientConfig {
+        proxy: Some(String::from("::not a url")),
+        ..Default::default()
+    };
+    let result = crate::config::client(&client);
+    assert_eq!(result.unwrap_err().code(), errors::CONFIG);
+
+    assert_eq!(errors::exit_code(&anyhow::anyhow!("other")), 1);
+}
+
+#[tokio::test]
+async fn rate_redirected_mirror() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::{mirrorlist::Output, tui::io::handler::IoAsyncHandler};
+
+    // moves every mirror under /old/ to /new/
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = [0; 1024];
+            let read = stream.read(&mut buf).await.unwrap_or_default();
+            let request = String::from_utf8_lossy(&buf[..read]);
+            let reply = match request.split_whitespace().nth(1) {
+                Some(path) if path.starts_with("/old/") => format!(
+                    "HTTP/1.1 301 Moved Permanently\r\nLocation: http://{address}{}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    path.replacen("/old/", "/new/", 1)
+                ),
+                _ => String::from("HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"),
+            };
+            let _ = stream.write_all(reply.as_bytes()).await;
+        }
+    });
+
+    let dir = std::env::temp_dir().join(format!("mirro-rs-redirect-{}", std::process::id()));
+    let config = Configuration {
+        outfile: dir.join("mirrorlist"),
+        export: 10,
+        ..Default::default()
+    };
+    let url = format!("http://{address}/old/");
+    IoAsyncHandler::rate_mirrors(
+        vec![Entry::from(url.clone())],
+        None,
+        None,
+        Output::from(&config),
+        None,
+        archlinux::get_client(None).unwrap(),
+        None,
+    )
+    .await
+    .await
+    .unwrap()
+    .unwrap();
+
+    // the mirror is exported under the URL it was selected with
+    let contents = tokio::fs::read_to_string(&config.outfile).await.unwrap();
+    assert!(contents.contains(&format!("Server = {url}")));
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+}
+
+#[test]
+fn list_formats() {
+    use archlinux::Country;
+
+    use crate::{
+        cli::{Column, ListFormat},
+        commands::list::render,
+    };
+
+    let mirrorlist =
+        archlinux::parse_local(include_str!("../../../archlinux/sample/archlinux.json")).unwrap();
+    let mut mirror = mirrorlist.countries[0].mirrors[0].clone();
+    mirror.delay = Some(90);
+    mirror.score = None;
+    let country = |name: &str, code: &str| Country {
+        name: name.to_string(),
+        code: code.to_string(),
+        mirrors: vec![],
+    };
+    let (korea, quoted) = (
+        country("Korea, Republic of", "KR"),
+        country("The \"Best\" Country", "BC"),
+    );
+    let mirrors = [(&korea, &mirror), (&quoted, &mirror)];
+
+    // fields with commas or quotes are quoted, and missing values are empty
+    let columns = [Column::Country, Column::Delay, Column::Score];
+    assert_eq!(
+        render(ListFormat::Csv, &columns, &mirrors).unwrap(),
+        "country,delay,score\n\"Korea, Republic of\",90,\n\"The \"\"Best\"\" Country\",90,\n"
+    );
+
+    // every column is as wide as its widest cell, header included
+    let columns = [Column::Code, Column::Delay, Column::Score];
+    assert_eq!(
+        render(ListFormat::Table, &columns, &mirrors).unwrap(),
+        "code  delay  score\nKR    90     -\nBC    90     -\n"
+    );
+
+    // columns come in the order they are asked for
+    let columns = [Column::Score, Column::Code];
+    assert_eq!(
+        render(ListFormat::Table, &columns, &mirrors).unwrap(),
+        "score  code\n-      KR\n-      BC\n"
+    );
+    assert_eq!(
+        render(ListFormat::Csv, &columns, &mirrors[..1]).unwrap(),
+        "score,code\n,KR\n"
+    );
+
+    let ndjson = render(ListFormat::Ndjson, &columns, &mirrors).unwrap();
+    let rows = ndjson
+        .lines()
+        .map(|f| serde_json::from_str::<serde_json::Value>(f).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        rows,
+        [
+            serde_json::json!({ "score": null, "code": "KR" }),
+            serde_json::json!({ "score": null, "code": "BC" })
+        ]
+    );
+    let json: serde_json::Value =
+        serde_json::from_str(&render(ListFormat::Json, &columns, &mirrors).unwrap()).unwrap();
+    assert_eq!(json, serde_json::Value::Array(rows));
+}
+
+#[test]
+fn config_sources() {
+    use crate::config::sources::{resolve, Layer, Source};
+    use serde_json::json;
+
+    let mut args = ArgConfig::default();
+    args.general.export = Some(7);
+    args.general.timeout = Some(2);
+    let layers = [
+        Layer::new(
+            Source::CommandLine,
+            json!({ "general": { "timeout": 2 }, "filters": {} }),
+        ),
+        Layer::environment(&args),
+        Layer::new(
+            Source::Profile(String::from("ci")),
+            json!({ "general": { "export": 5, "latest": 20 } }),
+        ),
+        Layer::new(
+            Source::File,
+            json!({ "general": { "latest": 10, "cache-ttl": 12 }, "filters": { "age": 6 } }),
+        ),
+        Layer::defaults(),
+    ];
+    let settings = resolve(&layers);
+    let setting = |key: &str| {
+        settings
+            .iter()
+            .find(|f| f.0 == key)
+            .map(|(_, value, source)| (value.clone(), source.to_string()))
+            .unwrap()
+    };
+
+    assert_eq!(
+        setting("general.timeout"),
+        (json!(2), "command line".into())
+    );
+    assert_eq!(setting("general.export"), (json!(7), "environment".into()));
+    assert_eq!(setting("general.latest"), (json!(20), "profile ci".into()));
+    assert_eq!(setting("general.cache-ttl"), (json!(12), "file".into()));
+    assert_eq!(setting("filters.age"), (json!(6), "file".into()));
+    assert_eq!(setting("general.url").1, "default");
+    assert_eq!(setting("general.outfile"), (json!(null), "default".into()));
+    // flags that are off do not hide lower layers
+    assert_eq!(
+        setting("general.rate-speed"),
+        (json!(false), "default".into())
+    );
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn config_template() {
+    use crate::{cli::ConfigFormat, commands::config::template, config::read_config_file};
+
+    let template = template(ConfigFormat::Toml);
+    // every setting is described, whether it is set or not
+    let lines = template
+        .lines()
+        .skip_while(|f| !f.starts_with('['))
+        .collect::<Vec<_>>();
+    for (idx, line) in lines.iter().enumerate() {
+        if !line.is_empty() && !line.starts_with('[') && !line.starts_with("# ") {
+            assert!(
+                lines[idx - 1].starts_with("# "),
+                "{line} has no description"
+            );
+        }
+    }
+
+    let path = std::env::temp_dir().join(format!("mirro-rs-template-{}.toml", std::process::id()));
+    std::fs::write(&path, &template).unwrap();
+    let (args, file) = read_config_file(Some(&path), None).unwrap();
+    assert!(file.unwrap().issues().is_empty());
+    let config = Configuration::from((ArgConfig::default(), args));
+    let defaults: ArgConfig = toml::from_str("[general]\n[filters]").unwrap();
+    let defaults = Configuration::from((ArgConfig::default(), defaults));
+    assert_eq!(config.export, defaults.export);
+    assert_eq!(config.ttl, defaults.ttl);
+    assert_eq!(config.sort, defaults.sort);
+    assert_eq!(config.filters, defaults.filters);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn reload_keeps_command_line() {
+    use clap::Parser;
+    use std::sync::Mutex;
+
+    let args = ArgConfig::parse_from(["mirro-rs", "--export", "7", "--outfile", "/tmp/mirrorlist"]);
+    let file: ArgConfig =
+        toml::from_str("[general]\nexport = 3\ncache-ttl = 5\n[filters]").unwrap();
+    let config = Mutex::new(Configuration::from((args.clone(), ArgConfig::default())));
+
+    crate::config::watch::reload(&args, file, &config);
+    let config = config.into_inner().unwrap();
+    assert_eq!(config.export, 7);
+    assert_eq!(config.ttl, 5);
+    assert_eq!(config.outfile, std::path::PathBuf::from("/tmp/mirrorlist"));
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn tri_state_flags() {
+    use crate::config::fold_negations;
+    use clap::Parser;
+
+    let config: ArgConfig = toml::from_str(
+        r#"
+[general]
+rate-speed = true
+direct = true
+no-header = true
+strict = true
+one-per-host = true
+no-env-proxy = true
+
+[filters]
+protocols = [ "https" ]
+ipv4 = "exclude"
+isos = true
+"#,
+    )
+    .unwrap();
+
+    // the command line turns off what the file turned on
+    let mut args = ArgConfig::try_parse_from([
+        "mirro-rs",
+        "--no-rate",
+        "--direct=false",
+        "--header",
+        "--no-strict",
+        "--one-per-host=false",
+        "--env-proxy",
+        "--no-ipv4",
+        "--isos=exclude",
+    ])
+    .unwrap();
+    fold_negations(&mut args);
+    let merged = Configuration::from((args, config));
+    assert!(!merged.rate);
+    assert!(!merged.direct);
+    assert!(merged.header);
+    assert!(!merged.strict);
+    assert!(!merged.one_per_host);
+    assert!(!merged.client.ignore_env_proxy);
+    assert_eq!(
+        merged.filters,
+        [Protocol::Https, Protocol::Ipv6, Protocol::ExcludeIsos]
+    );
+
+    // and leaves the rest to the file
+    let config: ArgConfig =
+        toml::from_str("[general]\nrate-speed = true\n[filters]\nipv4 = \"exclude\"").unwrap();
+    let mut args = ArgConfig::try_parse_from(["mirro-rs", "-r", "export"]).unwrap();
+    fold_negations(&mut args);
+    assert!(args.command.is_some());
+    let merged = Configuration::from((args, config));
+    assert!(merged.rate);
+    assert!(!merged.direct);
+    assert!(merged.filters.contains(&Protocol::ExcludeIpv4));
+
+    assert!(ArgConfig::try_parse_from(["mirro-rs", "--isos=maybe"]).is_err());
+}
@@ -24,12 +24,14 @@ pub enum Action {
     SelectionSortDelay,
     SelectionSortDuration,
     SelectionSortScore,
+    ToggleOnePerHost,
+    PreviewExport,
     Export,
 }
 
 impl Action {
     pub fn iterator() -> Iter<'static, Action> {
-        static ACTIONS: [Action; 21] = [
+        static ACTIONS: [Action; 23] = [
             Action::Quit,
             Action::ClosePopUp,
             Action::ShowInput,
@@ -50,6 +52,8 @@ impl Action {
             Action::SelectionSortDelay,
             Action::SelectionSortDuration,
             Action::SelectionSortScore,
+            Action::ToggleOnePerHost,
+            Action::PreviewExport,
             Action::Export,
         ];
         ACTIONS.iter()
@@ -74,6 +78,8 @@ impl Action {
             Action::SelectionSortDelay => &[Key::Char('6')],
             Action::SelectionSortDuration => &[Key::Char('7')],
             Action::SelectionSortScore => &[Key::Char('8')],
+            Action::ToggleOnePerHost => &[Key::Ctrl('g')],
+            Action::PreviewExport => &[Key::Ctrl('d')],
             Action::Export => &[Key::Ctrl('e')],
             Action::FilterIpv4 => &[Key::Ctrl('4')],
             Action::FilterIpv6 => &[Key::Ctrl('6')],
@@ -102,10 +108,12 @@ impl Display for Action {
             Action::SelectionSortDelay => "sort [selection] delay",
             Action::SelectionSortDuration => "sort [selection] duration",
             Action::SelectionSortScore => "sort [selection] score",
+            Action::ToggleOnePerHost => "toggle one per host",
+            Action::PreviewExport => "preview export",
             Action::Export => "export mirrors",
-            Action::FilterIpv4 => "toggle ipv4",
-            Action::FilterIpv6 => "toggle ipv6",
-            Action::FilterIsos => "toggle isos",
+            Action::FilterIpv4 => "cycle ipv4 with/without/any",
+            Action::FilterIpv6 => "cycle ipv6 with/without/any",
+            Action::FilterIsos => "cycle isos with/without/any",
         };
         write!(f, "{str}")
     }
@@ -16,7 +16,10 @@ use tokio::sync::Mutex;
 use tracing::{error, info, warn};
 
 use crate::{
-    config::Configuration,
+    cli::Placement,
+    config::{countries, Configuration},
+    errors::Error,
+    mirrorlist::{self, managed, Backups, Diff, Entry, Output, Preview},
     tui::state::{App, PopUpState},
 };
 
@@ -28,11 +31,22 @@ pub struct IoAsyncHandler {
     app: Arc<Mutex<App>>,
     popup: Arc<Mutex<PopUpState>>,
     client: Client,
+    timeout: Option<u64>,
 }
 
 impl IoAsyncHandler {
-    pub fn new(app: Arc<Mutex<App>>, popup: Arc<Mutex<PopUpState>>, client: Client) -> Self {
-        Self { app, popup, client }
+    pub fn new(
+        app: Arc<Mutex<App>>,
+        popup: Arc<Mutex<PopUpState>>,
+        client: Client,
+        timeout: Option<u64>,
+    ) -> Self {
+        Self {
+            app,
+            popup,
+            client,
+            timeout,
+        }
     }
 
     pub async fn initialise(&mut self, config: Arc<std::sync::Mutex<Configuration>>) -> Result<()> {
@@ -107,28 +121,92 @@ impl IoAsyncHandler {
         popup_state.visible = true;
         std::mem::drop(popup_state);
 
-        let (check_dl_speed, outfile, export_count, mut selected_mirrors, extra_urls, age) = {
+        let (check_dl_speed, output, selected_mirrors) = self.selection().await;
+
+        if !check_dl_speed {
+            Self::write_to_file(
+                output,
+                &selected_mirrors,
+                Some(in_progress),
+                Some(Arc::clone(&self.popup)),
+            )
+            .await?;
+        } else {
+            let rating = Self::rate_mirrors(
+                selected_mirrors,
+                Some(Arc::clone(&self.popup)),
+                Some(progress_transmitter),
+                output,
+                Some(in_progress),
+                self.client.clone(),
+                self.timeout,
+            )
+            .await;
+            // rating carries on in the background, keeping the TUI responsive
+            tokio::spawn(async move {
+                if let Ok(Err(e)) = rating.await {
+                    error!("{e}");
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Shows what exporting the current selection (without rating it) would
+    /// change in the outfile
+    pub async fn preview(&self) -> Result<()> {
+        let mut popup_state = self.popup.lock().await;
+        popup_state.popup_text = String::from("Comparing your mirrors, please wait...");
+        popup_state.visible = true;
+        std::mem::drop(popup_state);
+
+        let (_, output, selected_mirrors) = self.selection().await;
+
+        Self::write_to_file(
+            Output {
+                preview: Preview {
+                    dry_run: true,
+                    diff: true,
+                },
+                ..output
+            },
+            &selected_mirrors,
+            None,
+            Some(Arc::clone(&self.popup)),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// The selected mirrors (and reachable included URLs), along with the
+    /// export settings
+    async fn selection(&self) -> (bool, Output, Vec<Entry>) {
+        let (check_dl_speed, output, mut selected_mirrors, extra_urls, age) = {
             let app_state = self.app.lock().await;
             let configuration = app_state.configuration.lock().unwrap();
             let check_dl_speed = configuration.rate;
-            let outfile = configuration.outfile.clone();
-            let export_count = configuration.export as usize;
+            let mut output = Output::from(&*configuration);
+            if let Some(ref mut header) = output.header {
+                header.last_check = app_state.mirrors.as_ref().map(|f| f.last_check);
+            }
             let include = configuration.include.clone();
             let age = configuration.age;
 
             let selected_mirrors = app_state
-                .selected_mirrors
+                .export_selection(&configuration)
                 .iter()
-                .map(|f| f.url.to_owned())
+                .map(|f| Entry {
+                    url: f.url.to_owned(),
+                    country: Some(f.country.to_owned()),
+                    protocol: Some(f.protocol),
+                    score: f.score,
+                    delay: f.delay,
+                    rate: None,
+                })
                 .collect_vec();
-            (
-                check_dl_speed,
-                outfile,
-                export_count,
-                selected_mirrors,
-                include,
-                age,
-            )
+            (check_dl_speed, output, selected_mirrors, include, age)
         };
 
         let client = self.client.clone();
@@ -141,50 +219,31 @@ impl IoAsyncHandler {
             }
         });
 
-        if let Ok(Some(Ok(mut item))) = included_urls.await {
-            selected_mirrors.append(&mut item)
+        if let Ok(Some(Ok(item))) = included_urls.await {
+            selected_mirrors.extend(item.into_iter().map(Entry::from))
         }
 
-        if !check_dl_speed {
-            Self::write_to_file(
-                outfile,
-                &selected_mirrors,
-                export_count,
-                Some(in_progress),
-                Some(Arc::clone(&self.popup)),
-            )
-            .await;
-        } else {
-            Self::rate_mirrors(
-                selected_mirrors,
-                Some(Arc::clone(&self.popup)),
-                Some(progress_transmitter),
-                outfile,
-                export_count,
-                Some(in_progress),
-                self.client.clone(),
-            )
-            .await;
-        }
-
-        Ok(())
+        (check_dl_speed, output, selected_mirrors)
     }
 
     pub async fn rate_mirrors(
-        selected_mirrors: Vec<String>,
+        selected_mirrors: Vec<Entry>,
         popup: Option<Arc<Mutex<PopUpState>>>,
         progress_transmitter: Option<std::sync::mpsc::Sender<f32>>,
-        outfile: PathBuf,
-        export_count: usize,
+        output: Output,
         in_progress: Option<Arc<AtomicBool>>,
         client: Client,
-    ) -> tokio::task::JoinHandle<()> {
+        timeout: Option<u64>,
+    ) -> tokio::task::JoinHandle<Result<(), Error>> {
         let mut mirrors = Vec::with_capacity(selected_mirrors.len());
 
         let mut set = tokio::task::JoinSet::new();
 
-        for i in selected_mirrors.iter() {
-            set.spawn(archlinux::rate_mirror(i.clone(), client.clone()));
+        // rating follows redirects, so the rated URL may not be the entry's
+        for (idx, i) in selected_mirrors.iter().enumerate() {
+            let rating =
+                archlinux::rate_mirror_with_timeout(i.url.clone(), client.clone(), timeout);
+            set.spawn(async move { (idx, rating.await) });
         }
 
         let popup_state = popup.clone();
@@ -195,10 +254,10 @@ impl IoAsyncHandler {
 
             while let Some(res) = set.join_next().await {
                 match res {
-                    Ok(Ok((duration, url))) => {
-                        mirrors.push((duration, url));
+                    Ok((idx, Ok((duration, _)))) => {
+                        mirrors.push((duration, idx));
                     }
-                    Ok(Err(cause)) => match cause {
+                    Ok((_, Err(cause))) => match cause {
                         archlinux::Error::Connection(e) => {
                             error!("{e}");
                         }
@@ -214,9 +273,19 @@ impl IoAsyncHandler {
                                     "could not locate {qualified_url} from {url}, reason=> {status_code}",
                                 );
                         }
+                        archlinux::Error::Probe {
+                            qualified_url,
+                            url,
+                            reason,
+                        } => {
+                            error!("could not probe {qualified_url} from {url}, reason=> {reason}");
+                        }
                         archlinux::Error::Request(e) => {
                             error!("{e}");
                         }
+                        archlinux::Error::Timestamp(e) => {
+                            error!("invalid timestamp {e}")
+                        }
                         archlinux::Error::TimeError(e) => {
                             error!("{e}")
                         }
@@ -230,61 +299,171 @@ impl IoAsyncHandler {
                 }
             }
 
-            let results = {
-                if !mirrors.is_empty() {
-                    mirrors.sort_by(|(duration_a, _), (duration_b, _)| duration_a.cmp(duration_b));
-
-                    mirrors.iter().map(|(_, url)| url.to_owned()).collect_vec()
-                } else {
-                    warn!("Exporting mirrors without rating...");
-                    selected_mirrors.to_vec()
+            let failed = len - mirrors.len();
+            let result = if len != 0 && failed == len {
+                Err(Error::Rating { total: len })
+            } else if failed != 0 && output.strict {
+                Err(Error::PartialRating { failed, total: len })
+            } else {
+                if failed != 0 {
+                    warn!("{failed} of {len} mirrors could not be rated and were left out");
                 }
+                mirrors.sort_by_key(|(duration, _)| *duration);
+
+                let results = mirrors
+                    .iter()
+                    .map(|(duration, idx)| Entry {
+                        rate: Some(*duration),
+                        ..selected_mirrors[*idx].clone()
+                    })
+                    .collect_vec();
+
+                Self::write_to_file(output, &results, in_progress.clone(), popup_state.clone())
+                    .await
             };
 
-            Self::write_to_file(outfile, &results, export_count, in_progress, popup_state).await;
+            if let Err(ref e @ (Error::Rating { .. } | Error::PartialRating { .. })) = result {
+                // nothing was written, so the export has to be wrapped up here
+                if let Some(popup) = popup_state {
+                    let mut state = popup.lock().await;
+                    state.popup_text = format!("Your mirrorlist was not exported: {e}");
+                }
+                if let Some(in_progress) = in_progress {
+                    in_progress.store(false, std::sync::atomic::Ordering::Relaxed);
+                }
+            }
 
             if let Some(progress) = progress_transmitter {
                 let _ = progress.send(0.0); // reset progress
             }
+            result
         })
     }
 
     pub async fn write_to_file(
-        outfile: PathBuf,
-        selected_mirrors: &[String],
-        export_count: usize,
+        output: Output,
+        selected_mirrors: &[Entry],
         in_progress: Option<Arc<AtomicBool>>,
         popup: Option<Arc<Mutex<PopUpState>>>,
+    ) -> Result<(), Error> {
+        let Output {
+            outfile,
+            count,
+            quota,
+            preview,
+            backups,
+            force,
+            strict,
+            placement,
+            header,
+            comments,
+        } = output;
+        let selected_mirrors = quota.apply(selected_mirrors, count);
+        let servers = mirrorlist::servers(&selected_mirrors);
+        let block = mirrorlist::render(&selected_mirrors, header.as_ref(), comments);
+
+        let result = if preview.dry_run || preview.diff {
+            Self::preview_file(&outfile, &servers, &block, preview, placement, popup).await;
+            Ok(())
+        } else if servers.is_empty() && !force {
+            if let Some(popup) = popup {
+                let mut state = popup.lock().await;
+                state.popup_text = String::from("There are no mirrors to export");
+            }
+            Err(Error::NoMirrors { path: outfile })
+        } else {
+            info!(count = %count, "making export of mirrors");
+            let result = replace_outfile(&outfile, &block, placement, &backups, strict).await;
+            let text = if result.is_err() {
+                format!(
+                    "Your mirrorlist could not be exported to: {}",
+                    outfile.display()
+                )
+            } else {
+                info!("Your mirrorlist has been exported");
+                format!(
+                    "Your mirrorlist has been successfully exported to: {}",
+                    outfile.display()
+                )
+            };
+            if let Some(popup) = popup {
+                let mut state = popup.lock().await;
+                state.popup_text = text;
+            }
+            result
+        };
+        if let Some(in_progress) = in_progress {
+            in_progress.store(false, std::sync::atomic::Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// Reports the would-be mirrorlist and how it differs from `outfile`
+    /// without writing anything. Outside of the TUI (no `popup`), the
+    /// mirrorlist goes to stdout, as does the diff unless both are requested
+    async fn preview_file(
+        outfile: &PathBuf,
+        servers: &[String],
+        block: &str,
+        preview: Preview,
+        placement: Placement,
+        popup: Option<Arc<Mutex<PopUpState>>>,
     ) {
-        if let Some(dir) = outfile.parent() {
-            info!(count = %export_count, "making export of mirrors");
-            if tokio::fs::create_dir_all(dir).await.is_ok() {
-                let output = &selected_mirrors[if selected_mirrors.len() >= export_count {
-                    ..export_count
-                } else {
-                    ..selected_mirrors.len()
-                }];
-                let output: Vec<_> = output
-                    .iter()
-                    .map(|f| format!("Server = {f}$repo/os/$arch"))
-                    .collect();
+        let current = match tokio::fs::read_to_string(outfile).await {
+            Ok(contents) => Some(contents),
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    error!("could not read {}: {e}", outfile.display());
+                }
+                None
+            }
+        };
 
-                if let Err(e) = tokio::fs::write(&outfile, output.join("\n")).await {
+        let diff = if preview.diff {
+            match current.as_deref().map(managed::block).transpose() {
+                Ok(block) => Some(Diff::new(
+                    &mirrorlist::parse(&block.unwrap_or_default()),
+                    servers,
+                )),
+                Err(e) => {
                     error!("{e}");
-                } else {
-                    info!("Your mirrorlist has been exported");
+                    None
                 }
-                if let Some(popup) = popup {
-                    let mut state = popup.lock().await;
-                    state.popup_text = format!(
-                        "Your mirrorlist has been successfully exported to: {}",
-                        outfile.display()
-                    );
+            }
+        } else {
+            None
+        };
+
+        match popup {
+            Some(popup) => {
+                if let Some(ref diff) = diff {
+                    for line in diff.to_string().lines() {
+                        info!("{line}");
+                    }
+                }
+                let mut state = popup.lock().await;
+                state.popup_text = format!(
+                    "{} servers would be exported to: {}\n{}",
+                    servers.len(),
+                    outfile.display(),
+                    diff.map(|f| f.to_string()).unwrap_or_default()
+                );
+            }
+            None => {
+                if let Some(diff) = diff {
+                    if preview.dry_run {
+                        eprintln!("{diff}");
+                    } else {
+                        println!("{diff}");
+                    }
+                }
+                if preview.dry_run {
+                    match managed::splice(current.as_deref(), block, placement) {
+                        Ok(contents) => print!("{contents}"),
+                        Err(e) => error!("{e}"),
+                    }
                 }
             }
-        }
-        if let Some(in_progress) = in_progress {
-            in_progress.store(false, std::sync::atomic::Ordering::Relaxed);
         }
     }
 
@@ -307,6 +486,7 @@ impl IoAsyncHandler {
                 in_progress,
                 progress_transmitter,
             } => self.export(in_progress, progress_transmitter).await,
+            IoEvent::Preview => self.preview().await,
         } {
             error!("{e}");
         }
@@ -315,6 +495,53 @@ impl IoAsyncHandler {
     }
 }
 
+/// Backs `outfile` up, then writes the new block to it. A failed backup only
+/// stops the export in strict mode
+async fn replace_outfile(
+    outfile: &PathBuf,
+    block: &str,
+    placement: Placement,
+    backups: &Backups,
+    strict: bool,
+) -> Result<(), Error> {
+    let failed = |path: &std::path::Path, e: &dyn std::fmt::Display| Error::Write {
+        path: path.to_path_buf(),
+        reason: format!("{e:#}"),
+    };
+
+    if let Some(dir) = outfile.parent() {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(|e| failed(dir, &e))?;
+    }
+    match backups.create(outfile).await {
+        Ok(Some(backup)) => info!("backed up {} to {}", outfile.display(), backup.display()),
+        Ok(None) => {}
+        Err(e) if strict => {
+            return Err(Error::Backup {
+                path: outfile.clone(),
+                reason: format!("{e:#}"),
+            })
+        }
+        Err(e) => warn!("could not back up {}: {e}", outfile.display()),
+    }
+    write_mirrorlist(outfile, block, placement)
+        .await
+        .map_err(|e| failed(outfile, &e))
+}
+
+/// Replaces the managed block in `outfile`, keeping the rest of it
+async fn write_mirrorlist(outfile: &PathBuf, block: &str, placement: Placement) -> Result<()> {
+    let current = match tokio::fs::read_to_string(outfile).await {
+        Ok(contents) => Some(contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    let contents = managed::splice(current.as_deref(), block, placement)?;
+    mirrorlist::write::atomic(outfile, contents.as_bytes()).await?;
+    Ok(())
+}
+
 async fn check_extra_urls(
     extra_urls: Vec<String>,
     age: u16,
@@ -350,17 +577,25 @@ async fn check_extra_urls(
     Ok(results)
 }
 
+/// Where the mirrorlist is cached, if the platform has a cache directory
+pub fn cache_file() -> Option<PathBuf> {
+    dirs::cache_dir().map(|mut cache| {
+        cache.push(env!("CARGO_PKG_NAME"));
+        cache.push(CACHE_FILE);
+        cache
+    })
+}
+
 // Do we get a new mirrorlist or nah
 pub async fn is_fresh(
     app: Arc<std::sync::Mutex<Configuration>>,
 ) -> (bool, Option<std::path::PathBuf>) {
-    if let Some(mut cache) = dirs::cache_dir() {
-        let crate_name = env!("CARGO_PKG_NAME");
-        cache.push(crate_name);
-        if let Err(e) = tokio::fs::create_dir_all(&cache).await {
-            error!("could not create cache directory, {e}");
+    if let Some(cache) = cache_file() {
+        if let Some(dir) = cache.parent() {
+            if let Err(e) = tokio::fs::create_dir_all(dir).await {
+                error!("could not create cache directory, {e}");
+            }
         }
-        cache.push(CACHE_FILE);
         if cache.exists() {
             let config = app.lock().unwrap();
             let expires = config.ttl;
@@ -416,8 +651,7 @@ async fn get_new_mirrors(
 
             show_stats(&mirrors.countries, false);
 
-            let mut app = app.lock().await;
-            app.mirrors = Some(mirrors);
+            update_state(app, Arc::clone(&config), mirrors).await;
         }
         Err(e) => {
             warn!("{e}, using old cached file fallback");
@@ -445,16 +679,14 @@ async fn update_state(
 ) {
     let mut app = app.lock().await;
     let config = config.lock().unwrap();
+    if let Err(e) = countries::check(&mirrors.countries, &config.country) {
+        error!("{e}");
+    }
     if !config.country.is_empty() {
         let items = mirrors
             .countries
             .into_iter()
-            .filter(|f| {
-                config
-                    .country
-                    .iter()
-                    .any(|a| a.eq_ignore_ascii_case(&f.name))
-            })
+            .filter(|f| config.country.iter().any(|a| countries::matches(a, f)))
             .collect_vec();
         mirrors.countries = items;
     }
@@ -5,6 +5,7 @@ pub mod handler;
 pub enum IoEvent {
     Initialise,
     ClosePopUp,
+    Preview,
     Export {
         in_progress: Arc<AtomicBool>,
         progress_transmitter: Sender<f32>,
@@ -7,7 +7,6 @@ pub mod view;
 
 use anyhow::Result;
 
-use archlinux::get_client;
 use crossterm::{
     event::{DisableMouseCapture, EnableMouseCapture},
     execute,
@@ -22,7 +21,7 @@ use tracing::debug;
 
 use ratatui::{backend::CrosstermBackend, Terminal};
 
-use crate::config::Configuration;
+use crate::config::{self, Configuration};
 
 use self::{
     inputs::{event::Events, InputEvent},
@@ -35,10 +34,9 @@ pub async fn start(configuration: Arc<std::sync::Mutex<Configuration>>) -> Resul
     enable_raw_mode()?;
     let mut stdout = std::io::stdout();
 
-    let client = {
-        let timeout = configuration.lock().unwrap();
-        let timeout = timeout.connection_timeout;
-        get_client(timeout)?
+    let (client, timeout) = {
+        let config = configuration.lock().unwrap();
+        (config::client(&config.client)?, config.client.timeout)
     };
 
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
@@ -53,7 +51,7 @@ pub async fn start(configuration: Arc<std::sync::Mutex<Configuration>>) -> Resul
     {
         let popup_state = Arc::clone(&popup_state);
         tokio::spawn(async move {
-            let mut handler = IoAsyncHandler::new(inner, popup_state, client);
+            let mut handler = IoAsyncHandler::new(inner, popup_state, client, timeout);
             debug!("Getting Arch Linux mirrors. Please wait");
             while let Some(io_event) = sync_io_rx.recv().await {
                 handler
@@ -1,11 +1,8 @@
-use archlinux::{
-    chrono::{DateTime, Utc},
-    ArchLinux, Country,
-};
+use archlinux::{ArchLinux, Country};
 use std::sync::{atomic::AtomicBool, mpsc::Sender, Arc, Mutex};
 
 use crate::{
-    cli::{Protocol, ViewSort},
+    cli::{Protocol, SelectionSort, ViewSort},
     config::Configuration,
 };
 
@@ -58,13 +55,13 @@ impl PopUpState {
 
 #[derive(Debug, Clone)]
 pub struct SelectedMirror {
+    pub country: String,
     pub country_code: String,
-    pub protocol: Protocol,
+    pub protocol: archlinux::Protocol,
     pub completion_pct: f32,
     pub delay: Option<i64>,
     pub score: Option<f64>,
     pub duration_stddev: Option<f64>,
-    pub last_sync: Option<DateTime<Utc>>,
     pub url: String,
 }
 
@@ -154,34 +151,11 @@ impl App {
                         AppReturn::Continue
                     }
                     Action::SelectionSortCompletionPct => {
-                        self.selected_mirrors
-                            .sort_by(|a, b| b.completion_pct.total_cmp(&a.completion_pct));
-                        AppReturn::Continue
-                    }
-                    Action::SelectionSortDelay => {
-                        self.selected_mirrors.sort_by(|a, b| {
-                            let a = a.delay.unwrap_or(i64::MAX);
-                            let b = b.delay.unwrap_or(i64::MAX);
-                            a.partial_cmp(&b).unwrap()
-                        });
-                        AppReturn::Continue
-                    }
-                    Action::SelectionSortScore => {
-                        self.selected_mirrors.sort_by(|a, b| {
-                            let a = a.score.unwrap_or(f64::MAX);
-                            let b = b.score.unwrap_or(f64::MAX);
-                            a.partial_cmp(&b).unwrap()
-                        });
-                        AppReturn::Continue
-                    }
-                    Action::SelectionSortDuration => {
-                        self.selected_mirrors.sort_by(|a, b| {
-                            let a = a.duration_stddev.unwrap_or(f64::MAX);
-                            let b = b.duration_stddev.unwrap_or(f64::MAX);
-                            a.partial_cmp(&b).unwrap()
-                        });
-                        AppReturn::Continue
+                        self.toggle_sort(SelectionSort::Percentage)
                     }
+                    Action::SelectionSortDelay => self.toggle_sort(SelectionSort::Delay),
+                    Action::SelectionSortScore => self.toggle_sort(SelectionSort::Score),
+                    Action::SelectionSortDuration => self.toggle_sort(SelectionSort::Duration),
                     Action::Export => {
                         if !exporting.load(std::sync::atomic::Ordering::Relaxed) {
                             if self.selected_mirrors.is_empty() {
@@ -198,9 +172,28 @@ impl App {
                         }
                         AppReturn::Continue
                     }
-                    Action::FilterIpv4 => insert_filter(self, Protocol::Ipv4),
-                    Action::FilterIpv6 => insert_filter(self, Protocol::Ipv6),
-                    Action::FilterIsos => insert_filter(self, Protocol::Isos),
+                    Action::PreviewExport => {
+                        if !exporting.load(std::sync::atomic::Ordering::Relaxed) {
+                            if self.selected_mirrors.is_empty() {
+                                warn!("You haven't selected any mirrors yet");
+                            } else {
+                                let _ = self.io_tx.send(IoEvent::Preview).await;
+                            }
+                        }
+                        AppReturn::Continue
+                    }
+                    Action::ToggleOnePerHost => {
+                        let mut config = self.configuration.lock().unwrap();
+                        config.one_per_host = !config.one_per_host;
+                        info!(
+                            "one mirror per host: {}",
+                            if config.one_per_host { "on" } else { "off" }
+                        );
+                        AppReturn::Continue
+                    }
+                    Action::FilterIpv4 => cycle_filter(self, Protocol::Ipv4, Protocol::ExcludeIpv4),
+                    Action::FilterIpv6 => cycle_filter(self, Protocol::Ipv6, Protocol::ExcludeIpv6),
+                    Action::FilterIsos => cycle_filter(self, Protocol::Isos, Protocol::ExcludeIsos),
                 }
             }
         } else {
@@ -289,6 +282,8 @@ impl App {
             Action::SelectionSortDelay,
             Action::SelectionSortDuration,
             Action::SelectionSortScore,
+            Action::ToggleOnePerHost,
+            Action::PreviewExport,
             Action::Export,
         ]
         .into();
@@ -314,7 +309,7 @@ impl App {
         iter.chunks(self.table_viewport_height.into()).collect_vec()
     }
 
-    pub fn rows(&self) -> Vec<Row> {
+    pub fn rows(&self) -> Vec<Row<'_>> {
         self.filtered_countries
             .iter()
             .enumerate()
@@ -340,7 +335,7 @@ impl App {
 
                 let index = format!("  {idx}│");
 
-                return Row::new([index, item_name, count.to_string()].iter().map(|c| {
+                Row::new([index, item_name, count.to_string()].iter().map(|c| {
                     Cell::from(c.clone()).style(if selected {
                         Style::default()
                             .add_modifier(Modifier::BOLD)
@@ -348,7 +343,7 @@ impl App {
                     } else {
                         Style::default().fg(Color::Gray)
                     })
-                }));
+                }))
             })
             .collect_vec()
     }
@@ -379,15 +374,15 @@ impl App {
             let mut mirrors = country
                 .mirrors
                 .iter()
-                .filter(|f| filter_result(self, f))
+                .filter(|f| filter_result(self, country, f))
                 .map(|f| SelectedMirror {
+                    country: country.name.to_string(),
                     country_code: country.code.to_string(),
-                    protocol: Protocol::from(f.protocol),
+                    protocol: f.protocol,
                     completion_pct: f.completion_pct,
                     delay: f.delay,
                     score: f.score,
                     duration_stddev: f.duration_stddev,
-                    last_sync: f.last_sync,
                     url: f.url.to_string(),
                 })
                 .collect_vec();
@@ -399,7 +394,10 @@ impl App {
                 .collect_vec();
 
             if pos.is_empty() {
-                self.selected_mirrors.append(&mut mirrors)
+                self.selected_mirrors.append(&mut mirrors);
+                // new mirrors are placed according to the configured sort
+                let config = self.configuration.lock().unwrap();
+                config.sort.sort(&mut self.selected_mirrors);
             } else {
                 let new_items = self
                     .selected_mirrors
@@ -418,6 +416,28 @@ impl App {
         }
     }
 
+    /// Adds, reverses or removes a key of the sort that is applied to the
+    /// selection and written in the header, see `SortSpec::toggle`
+    fn toggle_sort(&mut self, field: SelectionSort) -> AppReturn {
+        let mut config = self.configuration.lock().unwrap();
+        config.sort.toggle(field);
+        info!("sorting selection by {}", config.sort);
+        config.sort.sort(&mut self.selected_mirrors);
+        AppReturn::Continue
+    }
+
+    /// The selected mirrors that will be exported, keeping one per host if
+    /// configured to do so
+    pub fn export_selection(&self, config: &Configuration) -> Vec<&SelectedMirror> {
+        if config.one_per_host {
+            archlinux::one_per_host(&self.selected_mirrors, &config.prefer, |f| {
+                (f.url.as_str(), f.protocol)
+            })
+        } else {
+            self.selected_mirrors.iter().collect_vec()
+        }
+    }
+
     fn fragment_number(&self) -> usize {
         (self.scroll_pos / self.table_viewport_height as isize) as usize
     }
@@ -444,6 +464,25 @@ fn insert_filter(app: &mut App, filter: Protocol) -> AppReturn {
     AppReturn::Continue
 }
 
+/// Goes from keeping only the mirrors with a property, to keeping only those
+/// without it, to not filtering on it
+fn cycle_filter(app: &mut App, with: Protocol, without: Protocol) -> AppReturn {
+    let mut config = app.configuration.lock().unwrap();
+    if let Some(idx) = config.filters.iter().position(|f| *f == with) {
+        info!("protocol filter: {with} replaced by {without}");
+        config.filters[idx] = without;
+    } else if let Some(idx) = config.filters.iter().position(|f| *f == without) {
+        info!("protocol filter: removed {without}");
+        config.filters.remove(idx);
+    } else {
+        info!("protocol filter: added {with}");
+        config.filters.push(with);
+    }
+    app.show_insync = false;
+    app.scroll_pos = 0;
+    AppReturn::Continue
+}
+
 fn insert_sort(app: &mut App, view: ViewSort) -> AppReturn {
     let mut config = app.configuration.lock().unwrap();
     config.view = view;
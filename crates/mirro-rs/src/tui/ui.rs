@@ -5,7 +5,7 @@ use std::{
 
 use archlinux::{
     chrono::{DateTime, Local},
-    Mirror,
+    Country, Mirror,
 };
 
 use itertools::Itertools;
@@ -176,7 +176,11 @@ fn draw_table(app: &mut App, f: &mut Frame, region: Rect) {
             .countries
             .iter()
             .filter_map(|f| {
-                let count = f.mirrors.iter().filter(|m| filter_result(app, m)).count();
+                let count = f
+                    .mirrors
+                    .iter()
+                    .filter(|m| filter_result(app, f, m))
+                    .count();
                 if count == 0 {
                     None
                 } else if f
@@ -229,7 +233,7 @@ fn draw_table(app: &mut App, f: &mut Frame, region: Rect) {
     f.render_widget(t, region);
 }
 
-fn draw_help(actions: &Actions) -> Table {
+fn draw_help(actions: &Actions) -> Table<'_> {
     let key_style = Style::default().fg(Color::LightCyan);
     let help_style = Style::default().fg(Color::Gray);
 
@@ -308,7 +312,7 @@ fn draw_logs<'a>() -> TuiLoggerWidget<'a> {
         .block(create_block("Logs"))
 }
 
-fn draw_filter(app: &App) -> Paragraph {
+fn draw_filter(app: &App) -> Paragraph<'_> {
     Paragraph::new(app.input.as_str()).block(create_block("Filter"))
 }
 
@@ -318,7 +322,11 @@ fn draw_selection<'a>(app: &App) -> Table<'a> {
         .map(|h| Cell::from(*h).style(Style::default()));
     let headers = Row::new(header_cells);
 
-    let items = app.selected_mirrors.iter().map(|f| {
+    let config = app.configuration.lock().unwrap();
+    let selection = app.export_selection(&config);
+    let mirror_count = selection.len();
+
+    let items = selection.iter().map(|f| {
         let delay = f.delay.map(|f| {
             let duration = Duration::from_secs(f as u64);
             let minutes = (duration.as_secs() / 60) % 60;
@@ -386,8 +394,17 @@ fn draw_selection<'a>(app: &App) -> Table<'a> {
         ])
     });
 
-    let mirror_count = app.selected_mirrors.len();
-    let config = app.configuration.lock().unwrap();
+    let mut label = if config.one_per_host {
+        format!("{mirror_count} hosts")
+    } else {
+        mirror_count.to_string()
+    };
+    if let Some(max) = config.quota.max_per_country {
+        label.push_str(&format!(", ≤{max}/country"));
+    }
+    if let Some(min) = config.quota.min_countries {
+        label.push_str(&format!(", ≥{min} countries"));
+    }
 
     let t = Table::new(
         items,
@@ -406,11 +423,11 @@ fn draw_selection<'a>(app: &App) -> Table<'a> {
     .header(headers)
     // As any other widget, a Table can be wrapped in a Block.
     .block(create_block(if mirror_count < 1 {
-        format!("Selection({mirror_count})")
+        format!("Selection({label})")
     } else {
         format!(
             "Selection({})▶ ({}) to {}",
-            mirror_count,
+            label,
             if config.export as usize <= mirror_count {
                 config.export.to_string()
             } else {
@@ -441,6 +458,10 @@ fn draw_sort<'a>(app: &App) -> Paragraph<'a> {
             ret
         })
         .collect();
+    sorts.push(Span::styled(
+        format!(" [{}]", config.sort),
+        Style::default().fg(Color::Green),
+    ));
 
     let mut filters: Vec<_> = config
         .filters
@@ -467,7 +488,10 @@ fn draw_sort<'a>(app: &App) -> Paragraph<'a> {
 
     let widget = Line::from(sorts);
 
-    let bt = format!("Sort & Filter ({count})");
+    let bt = match config.profile {
+        Some(ref profile) => format!("Sort & Filter ({count}) · profile {profile}"),
+        None => format!("Sort & Filter ({count})"),
+    };
 
     Paragraph::new(widget).block(create_block(bt))
 }
@@ -493,9 +517,12 @@ fn format_float(str: impl ToString) -> f32 {
     }
 }
 
-pub fn filter_result(app: &App, f: &Mirror) -> bool {
+pub fn filter_result(app: &App, country: &Country, f: &Mirror) -> bool {
     use crate::config::Configuration;
     let mut config = app.configuration.lock().unwrap();
+    if !config.rules.allows(country, f) {
+        return false;
+    }
 
     let res = |config: &Configuration, f: &Mirror| {
         let mut completion_ok = config.completion_percent as f32 <= f.completion_pct * 100.0;
@@ -513,6 +540,16 @@ pub fn filter_result(app: &App, f: &Mirror) -> bool {
         if v6_on {
             completion_ok = completion_ok && f.ipv6;
         }
+
+        for (excluded, has) in [
+            (Protocol::ExcludeIpv4, f.ipv4),
+            (Protocol::ExcludeIpv6, f.ipv6),
+            (Protocol::ExcludeIsos, f.isos),
+        ] {
+            if config.filters.contains(&excluded) {
+                completion_ok = completion_ok && !has;
+            }
+        }
         completion_ok
     };
 
@@ -29,6 +29,20 @@ impl From<archlinux::Protocol> for Protocol {
     }
 }
 
+impl TryFrom<Protocol> for archlinux::Protocol {
+    type Error = Protocol;
+
+    fn try_from(value: Protocol) -> Result<Self, Self::Error> {
+        match value {
+            Protocol::Https => Ok(Self::Https),
+            Protocol::Http => Ok(Self::Http),
+            Protocol::Rsync => Ok(Self::Rsync),
+            Protocol::Ftp => Ok(Self::Ftp),
+            _ => Err(value),
+        }
+    }
+}
+
 impl Display for Protocol {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -43,6 +57,9 @@ impl Display for Protocol {
                 Protocol::Ipv4 => "ipv4",
                 Protocol::Ipv6 => "ipv6",
                 Protocol::Isos => "isos",
+                Protocol::ExcludeIpv4 => "exclude-ipv4",
+                Protocol::ExcludeIpv6 => "exclude-ipv6",
+                Protocol::ExcludeIsos => "exclude-isos",
             }
         )
     }
@@ -1,6 +1,10 @@
-use std::fmt::Display;
+use std::{cmp::Ordering, fmt::Display};
 
-use crate::cli::ViewSort;
+use crate::{
+    cli::{SelectionSort, SortKey, SortSpec, ViewSort},
+    direct::stages::Candidate,
+    tui::state::SelectedMirror,
+};
 
 impl Display for ViewSort {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -12,25 +16,115 @@ impl Display for ViewSort {
     }
 }
 
-#[allow(dead_code)]
-#[cfg_attr(test, derive(Default))]
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
-pub enum ExportSort {
-    Completion,
-    MirroringDelay,
-    #[cfg_attr(test, default)]
-    Duration,
-    Score,
+impl SortSpec {
+    /// Sorts by each key in turn, then by URL so that the order never depends
+    /// on the order the mirrors came in. Mirrors without a value go last
+    /// whichever the direction
+    pub fn sort<T: Statistics>(&self, mirrors: &mut [T]) {
+        mirrors.sort_by(|a, b| {
+            self.0
+                .iter()
+                .map(|key| {
+                    let value = match key.field {
+                        SelectionSort::Percentage => {
+                            Value::Present(a.completion_pct().total_cmp(&b.completion_pct()))
+                        }
+                        SelectionSort::Delay => compare(a.delay(), b.delay(), i64::cmp),
+                        SelectionSort::Duration => {
+                            compare(a.duration_stddev(), b.duration_stddev(), f64::total_cmp)
+                        }
+                        SelectionSort::Score => compare(a.score(), b.score(), f64::total_cmp),
+                    };
+                    match value {
+                        Value::Present(ordering) if key.descending => ordering.reverse(),
+                        Value::Present(ordering) | Value::Missing(ordering) => ordering,
+                    }
+                })
+                .find(|f| f.is_ne())
+                .unwrap_or_else(|| a.url().cmp(b.url()))
+        });
+    }
+
+    /// Adds `field` as the least significant key, best first. If it is
+    /// already there, it is reversed, then removed on the next toggle unless
+    /// it is the only key
+    pub fn toggle(&mut self, field: SelectionSort) {
+        let best = SortKey::best_first(field);
+        match self.0.iter().position(|f| f.field == field) {
+            None => self.0.push(best),
+            Some(idx) if self.0[idx] == best || self.0.len() == 1 => {
+                self.0[idx].descending = !self.0[idx].descending;
+            }
+            Some(idx) => {
+                self.0.remove(idx);
+            }
+        }
+    }
 }
 
-impl Display for ExportSort {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let str = match self {
-            ExportSort::Completion => "%",
-            ExportSort::MirroringDelay => "μ",
-            ExportSort::Duration => "σ",
-            ExportSort::Score => "~",
-        };
-        write!(f, "{str}")
+/// How two values compare, keeping apart the comparisons that involve a
+/// missing value as those are not reversed
+enum Value {
+    Present(Ordering),
+    Missing(Ordering),
+}
+
+fn compare<T>(a: Option<T>, b: Option<T>, cmp: impl Fn(&T, &T) -> Ordering) -> Value {
+    match (a, b) {
+        (Some(a), Some(b)) => Value::Present(cmp(&a, &b)),
+        (a, b) => Value::Missing(a.is_none().cmp(&b.is_none())),
+    }
+}
+
+/// The upstream statistics a mirror can be sorted on
+pub trait Statistics {
+    fn completion_pct(&self) -> f32;
+    fn delay(&self) -> Option<i64>;
+    fn duration_stddev(&self) -> Option<f64>;
+    fn score(&self) -> Option<f64>;
+    fn url(&self) -> &str;
+}
+
+impl Statistics for Candidate<'_> {
+    fn completion_pct(&self) -> f32 {
+        self.mirror.completion_pct
+    }
+
+    fn delay(&self) -> Option<i64> {
+        self.mirror.delay
+    }
+
+    fn duration_stddev(&self) -> Option<f64> {
+        self.mirror.duration_stddev
+    }
+
+    fn score(&self) -> Option<f64> {
+        self.mirror.score
+    }
+
+    fn url(&self) -> &str {
+        &self.mirror.url
+    }
+}
+
+impl Statistics for SelectedMirror {
+    fn completion_pct(&self) -> f32 {
+        self.completion_pct
+    }
+
+    fn delay(&self) -> Option<i64> {
+        self.delay
+    }
+
+    fn duration_stddev(&self) -> Option<f64> {
+        self.duration_stddev
+    }
+
+    fn score(&self) -> Option<f64> {
+        self.score
+    }
+
+    fn url(&self) -> &str {
+        &self.url
     }
 }